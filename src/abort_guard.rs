@@ -1,22 +1,13 @@
 use std::time::Duration;
 
-use js_sys::Function;
-use wasm_bindgen::{
-    JsCast, JsValue,
-    prelude::{Closure, wasm_bindgen},
-};
-use web_sys::{AbortController, AbortSignal};
-
-use crate::Error;
-
-#[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen(js_name = "setTimeout")]
-    fn set_timeout(handler: &Function, timeout: i32) -> JsValue;
+use wasm_bindgen::{JsCast, JsValue, prelude::Closure};
+use web_sys::{AbortController, AbortSignal, DomException};
 
-    #[wasm_bindgen(js_name = "clearTimeout")]
-    fn clear_timeout(handle: JsValue) -> JsValue;
-}
+use crate::{
+    Error,
+    error::TIMEOUT_ERROR,
+    timer::{clear_timeout, set_timeout, timeout_millis},
+};
 
 /// A guard that cancels a fetch request when dropped.
 pub struct AbortGuard {
@@ -39,11 +30,14 @@ impl AbortGuard {
     pub fn timeout(&mut self, timeout: Duration) {
         let ctrl = self.ctrl.clone();
         let abort = Closure::once(move || {
-            ctrl.abort_with_reason(&"tonic_web_wasm_client::Error::TimedOut".into())
+            match DomException::new_with_message_and_name("request timed out", TIMEOUT_ERROR) {
+                Ok(reason) => ctrl.abort_with_reason(&reason),
+                Err(_) => ctrl.abort(),
+            }
         });
         let timeout = set_timeout(
             abort.as_ref().unchecked_ref::<js_sys::Function>(),
-            timeout.as_millis().try_into().expect("timeout"),
+            timeout_millis(timeout),
         );
         if let Some((id, _)) = self.timeout.replace((timeout, abort)) {
             clear_timeout(id);
@@ -0,0 +1,205 @@
+use http::Uri;
+use js_sys::Reflect;
+use wasm_bindgen::JsValue;
+
+use crate::Error;
+
+/// Parses and validates `base_url`, resolving relative URLs against the location of the current document or worker
+///
+/// Returned URL has no trailing slash so that request paths can be appended to it.
+pub fn parse_base_url(base_url: &str) -> Result<String, Error> {
+    if is_absolute(base_url) {
+        resolve(base_url, None)
+    } else {
+        resolve(base_url, location_href().as_deref())
+    }
+}
+
+/// Joins `base_url` and path of a request
+pub fn join_url(base_url: &str, uri: &Uri) -> String {
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+
+    let mut url = String::with_capacity(base_url.len() + path.len());
+    url.push_str(base_url.trim_end_matches('/'));
+    if !path.starts_with('/') {
+        url.push('/');
+    }
+    url.push_str(path);
+
+    url
+}
+
+fn is_absolute(url: &str) -> bool {
+    url.split_once("://")
+        .is_some_and(|(scheme, _)| !scheme.is_empty() && !scheme.contains('/'))
+}
+
+fn resolve(base_url: &str, location: Option<&str>) -> Result<String, Error> {
+    let invalid = |reason: &str| Error::InvalidBaseUrl(format!("`{base_url}`: {reason}"));
+
+    if base_url.is_empty() {
+        return Err(invalid("URL is empty"));
+    }
+    if base_url.contains(['?', '#']) {
+        return Err(invalid("URL must not contain a query or fragment"));
+    }
+
+    let url = if is_absolute(base_url) {
+        base_url.to_string()
+    } else {
+        let location = location.ok_or_else(|| {
+            invalid("relative URL can only be used in a document or worker with a location")
+        })?;
+        let location = location
+            .parse::<Uri>()
+            .map_err(|_| invalid("failed to parse location"))?;
+        let (Some(scheme), Some(authority)) = (location.scheme_str(), location.authority()) else {
+            return Err(invalid("location is not an absolute URL"));
+        };
+
+        if let Some(url) = base_url.strip_prefix("//") {
+            format!("{scheme}://{url}")
+        } else if base_url.starts_with('/') {
+            format!("{scheme}://{authority}{}", remove_dot_segments(base_url))
+        } else {
+            let directory = &location.path()[..=location.path().rfind('/').unwrap_or(0)];
+            let path = remove_dot_segments(&format!("{directory}{base_url}"));
+            format!("{scheme}://{authority}{path}")
+        }
+    };
+
+    let uri = url
+        .parse::<Uri>()
+        .map_err(|_| invalid("failed to parse URL"))?;
+
+    match uri.scheme_str() {
+        Some("http" | "https") => {}
+        _ => return Err(invalid("URL scheme must be `http` or `https`")),
+    }
+    let authority = uri
+        .authority()
+        .filter(|authority| !authority.host().is_empty())
+        .ok_or_else(|| invalid("URL has no host"))?;
+
+    Ok(format!(
+        "{}://{}{}",
+        uri.scheme_str().unwrap_or_default(),
+        authority,
+        remove_dot_segments(uri.path()).trim_end_matches('/'),
+    ))
+}
+
+/// Removes `.` and `..` segments from an absolute path
+fn remove_dot_segments(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+
+    for segment in path.split('/').skip(1) {
+        match segment {
+            "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    // Keep trailing slash of paths ending with a dot segment
+    if path.ends_with("/.") || path.ends_with("/..") {
+        segments.push("");
+    }
+
+    format!("/{}", segments.join("/"))
+}
+
+/// Returns `location.href` of the global scope (available in documents and workers)
+fn location_href() -> Option<String> {
+    let location = Reflect::get(&js_sys::global(), &JsValue::from_str("location")).ok()?;
+    Reflect::get(&location, &JsValue::from_str("href"))
+        .ok()?
+        .as_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let location = Some("https://example.com/app/index.html");
+
+        let vals = [
+            (
+                "http://localhost:50051",
+                None,
+                Some("http://localhost:50051"),
+            ),
+            (
+                "http://localhost:50051/",
+                None,
+                Some("http://localhost:50051"),
+            ),
+            (
+                "https://example.com/api/grpc/",
+                None,
+                Some("https://example.com/api/grpc"),
+            ),
+            (
+                "https://example.com/a/../b",
+                None,
+                Some("https://example.com/b"),
+            ),
+            ("/grpc", location, Some("https://example.com/grpc")),
+            ("/", location, Some("https://example.com")),
+            ("grpc", location, Some("https://example.com/app/grpc")),
+            ("./grpc/", location, Some("https://example.com/app/grpc")),
+            ("../grpc", location, Some("https://example.com/grpc")),
+            (
+                "//api.example.com/grpc",
+                location,
+                Some("https://api.example.com/grpc"),
+            ),
+            ("/grpc", None, None),
+            ("", location, None),
+            ("ftp://example.com", None, None),
+            ("http://", None, None),
+            ("http://localhost:50051?query", None, None),
+            ("http://localhost:50051#fragment", None, None),
+            ("http://local host", None, None),
+        ];
+
+        for (base_url, location, expected) in vals.iter() {
+            let result = resolve(base_url, *location);
+
+            match expected {
+                Some(expected) => assert_eq!(result.unwrap(), *expected, "{base_url}"),
+                None => assert!(
+                    matches!(result, Err(Error::InvalidBaseUrl(_))),
+                    "{base_url}"
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_join_url() {
+        let vals = [
+            (
+                "http://localhost:50051",
+                "http://localhost:50051/echo.Echo/Echo",
+            ),
+            (
+                "http://localhost:50051/",
+                "http://localhost:50051/echo.Echo/Echo",
+            ),
+            (
+                "https://example.com/api/grpc",
+                "https://example.com/api/grpc/echo.Echo/Echo",
+            ),
+        ];
+
+        for (base_url, expected) in vals.iter() {
+            let uri = "/echo.Echo/Echo".parse().unwrap();
+            assert_eq!(join_url(base_url, &uri), *expected);
+        }
+    }
+}
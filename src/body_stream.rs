@@ -3,38 +3,89 @@ use std::{
     task::{Context, Poll},
 };
 
-use bytes::Bytes;
+use bytes::{BufMut, Bytes, BytesMut};
 use futures_util::{Stream, TryStreamExt, stream::empty};
 use http_body::{Body, Frame};
 use js_sys::Uint8Array;
+use wasm_bindgen::JsCast;
 use wasm_streams::readable::IntoStream;
+use web_sys::ReadableStream;
 
 use crate::{Error, abort_guard::AbortGuard};
 
+/// Chunk of a response body
+pub(crate) enum Chunk {
+    /// Chunk in Rust memory
+    Bytes(Bytes),
+    /// Chunk in JS memory
+    Js(Uint8Array),
+}
+
+impl Chunk {
+    /// Appends the chunk to `buf`
+    ///
+    /// Chunks in JS memory are copied directly into spare capacity of `buf` without any intermediate allocation.
+    pub fn copy_into(self, buf: &mut BytesMut) {
+        match self {
+            Chunk::Bytes(bytes) => buf.put(bytes),
+            Chunk::Js(array) => {
+                let len = array.length() as usize;
+
+                buf.reserve(len);
+                array.copy_to_uninit(&mut buf.spare_capacity_mut()[..len]);
+
+                // SAFETY: `copy_to_uninit` initialized first `len` bytes of spare capacity.
+                unsafe { buf.set_len(buf.len() + len) };
+            }
+        }
+    }
+
+    /// Converts the chunk into bytes
+    fn into_bytes(self) -> Bytes {
+        match self {
+            Chunk::Bytes(bytes) => bytes,
+            Chunk::Js(array) => array.to_vec().into(),
+        }
+    }
+}
+
+/// Body of a response returned by a [`FetchBackend`](crate::FetchBackend)
 pub struct BodyStream {
-    body_stream: Pin<Box<dyn Stream<Item = Result<Bytes, Error>>>>,
+    body_stream: Pin<Box<dyn Stream<Item = Result<Chunk, Error>>>>,
     _abort: Option<AbortGuard>,
 }
 
 impl BodyStream {
-    pub fn new(body_stream: IntoStream<'static>, abort: AbortGuard) -> Self {
-        let body_stream = body_stream
-            .map_ok(|js_value| {
-                let buffer = Uint8Array::new(&js_value);
+    pub(crate) fn new(body_stream: IntoStream<'static>, abort: AbortGuard) -> Self {
+        Self {
+            body_stream: Box::pin(js_chunks(body_stream)),
+            _abort: Some(abort),
+        }
+    }
 
-                let mut bytes_vec = vec![0; buffer.length() as usize];
-                buffer.copy_to(&mut bytes_vec);
+    /// Creates a body from a JavaScript `ReadableStream` of `Uint8Array`s (e.g. body of a `fetch` response)
+    pub fn from_readable_stream(body_stream: ReadableStream) -> Self {
+        let body_stream =
+            wasm_streams::ReadableStream::from_raw(body_stream.unchecked_into()).into_stream();
 
-                bytes_vec.into()
-            })
-            .map_err(Error::js_error);
+        Self {
+            body_stream: Box::pin(js_chunks(body_stream)),
+            _abort: None,
+        }
+    }
 
+    /// Creates a body from a stream of bytes
+    pub fn from_stream<S>(body_stream: S) -> Self
+    where
+        S: Stream<Item = Result<Bytes, Error>> + 'static,
+    {
         Self {
-            body_stream: Box::pin(body_stream),
-            _abort: Some(abort),
+            body_stream: Box::pin(body_stream.map_ok(Chunk::Bytes)),
+            _abort: None,
         }
     }
 
+    /// Creates an empty body
     pub fn empty() -> Self {
         let body_stream = empty();
 
@@ -43,6 +94,20 @@ impl BodyStream {
             _abort: None,
         }
     }
+
+    pub(crate) fn poll_chunk(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Chunk, Error>>> {
+        self.body_stream.as_mut().poll_next(cx)
+    }
+}
+
+fn js_chunks(body_stream: IntoStream<'static>) -> impl Stream<Item = Result<Chunk, Error>> {
+    // Chunks of `fetch` response bodies are always `Uint8Array`s
+    body_stream
+        .map_ok(|js_value| Chunk::Js(js_value.unchecked_into()))
+        .map_err(Error::stream_error)
 }
 
 impl Body for BodyStream {
@@ -51,15 +116,93 @@ impl Body for BodyStream {
     type Error = Error;
 
     fn poll_frame(
-        mut self: Pin<&mut Self>,
+        self: Pin<&mut Self>,
         cx: &mut Context<'_>,
-    ) -> Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
-        match self.body_stream.as_mut().poll_next(cx) {
-            Poll::Ready(maybe) => Poll::Ready(maybe.map(|result| result.map(Frame::data))),
-            Poll::Pending => Poll::Pending,
-        }
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        self.poll_chunk(cx)
+            .map(|chunk| chunk.map(|chunk| chunk.map(|chunk| Frame::data(chunk.into_bytes()))))
     }
 }
 
+// Bodies may hold JavaScript values or `!Send` streams. This is only sound on wasm targets without the `atomics` target
+// feature, which always run on a single thread.
+#[cfg(all(target_arch = "wasm32", not(target_feature = "atomics")))]
 unsafe impl Send for BodyStream {}
+#[cfg(all(target_arch = "wasm32", not(target_feature = "atomics")))]
 unsafe impl Sync for BodyStream {}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::{FutureExt, stream};
+    use http_body_util::BodyExt;
+
+    use super::*;
+
+    #[test]
+    fn test_body() {
+        let body = BodyStream::from_stream(stream::iter([
+            Ok(Bytes::from_static(b"hello ")),
+            Ok(Bytes::from_static(b"world")),
+        ]));
+
+        let collected = body.collect().now_or_never().unwrap().unwrap();
+        assert_eq!(collected.to_bytes(), &b"hello world"[..]);
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod benches {
+    use wasm_bindgen_test::{Criterion, wasm_bindgen_bench};
+
+    use super::*;
+
+    const CHUNK_SIZE: usize = 64 * 1024;
+    const CHUNK_COUNT: usize = 16;
+
+    fn chunks() -> Vec<Uint8Array> {
+        (0..CHUNK_COUNT)
+            .map(|i| Uint8Array::from(&vec![i as u8; CHUNK_SIZE][..]))
+            .collect()
+    }
+
+    /// Read path before reading directly into the buffer: every chunk is copied into a fresh `Vec` which is then
+    /// copied into the buffer.
+    #[wasm_bindgen_bench]
+    fn bench_copy_via_vec(c: &mut Criterion) {
+        let chunks = chunks();
+
+        c.bench_function("copy 1 MiB via Vec", |b| {
+            b.iter(|| {
+                let mut buf = BytesMut::new();
+
+                for chunk in chunks.iter() {
+                    let chunk = Uint8Array::new(chunk);
+
+                    let mut bytes_vec = vec![0; chunk.length() as usize];
+                    chunk.copy_to(&mut bytes_vec);
+
+                    buf.put(Bytes::from(bytes_vec));
+                }
+
+                buf
+            })
+        });
+    }
+
+    #[wasm_bindgen_bench]
+    fn bench_copy_into(c: &mut Criterion) {
+        let chunks = chunks();
+
+        c.bench_function("copy 1 MiB into buffer", |b| {
+            b.iter(|| {
+                let mut buf = BytesMut::new();
+
+                for chunk in chunks.iter() {
+                    Chunk::Js(chunk.clone()).copy_into(&mut buf);
+                }
+
+                buf
+            })
+        });
+    }
+}
@@ -0,0 +1,99 @@
+use std::rc::Rc;
+
+use tower_layer::{Identity, Layer, Stack};
+
+use crate::{
+    Client, Error,
+    base_url::parse_base_url,
+    fetch::{FetchBackend, JsFetch},
+    options::{ConcurrencyLimit, FetchOptions},
+};
+
+/// Builder for [`Client`] which validates the base URL and allows wrapping the client in `tower` layers
+///
+/// Base URL may be absolute (e.g. `https://example.com/api/grpc`) or relative to the location of the current document
+/// or worker (e.g. `/grpc`). Request paths are appended to the path of the base URL.
+///
+/// Layers are applied in the order they are added, i.e., the first added layer is the outermost one and sees requests
+/// first.
+///
+/// ```rust,ignore
+/// use tonic_web_wasm_client::{ClientBuilder, layer::AuthLayer};
+///
+/// let client = ClientBuilder::new(base_url)
+///     .layer(AuthLayer::new(|refresh| fetch_token(refresh)))
+///     .build()?;
+/// let query_client = QueryClient::new(client);
+/// ```
+#[derive(Clone)]
+pub struct ClientBuilder<L = Identity> {
+    base_url: String,
+    options: Option<FetchOptions>,
+    backend: Option<Rc<dyn FetchBackend>>,
+    concurrency_limit: Option<ConcurrencyLimit>,
+    layer: L,
+}
+
+impl ClientBuilder {
+    /// Creates a new builder
+    pub fn new(base_url: String) -> Self {
+        Self {
+            base_url,
+            options: None,
+            backend: None,
+            concurrency_limit: None,
+            layer: Identity::new(),
+        }
+    }
+}
+
+impl<L> ClientBuilder<L> {
+    /// Sets the options for the client
+    pub fn options(mut self, options: FetchOptions) -> Self {
+        self.options = Some(options);
+        self
+    }
+
+    /// Sets the backend used for sending requests (defaults to [`JsFetch`])
+    pub fn backend(mut self, backend: impl FetchBackend + 'static) -> Self {
+        self.backend = Some(Rc::new(backend));
+        self
+    }
+
+    /// Sets limits of concurrent calls made by the client (calls are not limited by default)
+    pub fn concurrency_limit(mut self, concurrency_limit: ConcurrencyLimit) -> Self {
+        self.concurrency_limit = Some(concurrency_limit);
+        self
+    }
+
+    /// Adds a layer wrapping the client (and all layers added after this one)
+    pub fn layer<T>(self, layer: T) -> ClientBuilder<Stack<T, L>> {
+        ClientBuilder {
+            base_url: self.base_url,
+            options: self.options,
+            backend: self.backend,
+            concurrency_limit: self.concurrency_limit,
+            layer: Stack::new(layer, self.layer),
+        }
+    }
+
+    /// Builds the client wrapped in all added layers
+    ///
+    /// Returns [`Error::InvalidBaseUrl`] if the base URL is not a valid `http` or `https` URL.
+    pub fn build(self) -> Result<L::Service, Error>
+    where
+        L: Layer<Client>,
+    {
+        let mut client = Client::from_parts(
+            parse_base_url(&self.base_url)?,
+            self.options,
+            self.backend.unwrap_or_else(|| Rc::new(JsFetch::new())),
+        );
+
+        if let Some(concurrency_limit) = self.concurrency_limit {
+            client.with_concurrency_limit(concurrency_limit);
+        }
+
+        Ok(self.layer.layer(client))
+    }
+}
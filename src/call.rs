@@ -1,117 +1,744 @@
+use std::{
+    future::{poll_fn, ready},
+    pin::Pin,
+    time::Duration,
+};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use bytes::{Bytes, BytesMut};
+use futures_util::{Stream, StreamExt, stream};
 use http::{
-    HeaderMap, HeaderValue, Request, Response,
+    HeaderMap, HeaderValue, Method, Request, Response, StatusCode,
     header::{ACCEPT, CONTENT_TYPE},
-    response::Builder,
 };
 use http_body_util::BodyExt;
-use js_sys::{Array, Uint8Array};
-use tonic::body::Body;
-use wasm_bindgen::JsValue;
-use web_sys::{Headers, RequestCredentials, RequestInit};
+use tonic::{Code, Status, body::Body};
+
+use crate::{
+    Error, ResponseBody,
+    base_url::join_url,
+    body_stream::BodyStream,
+    connect,
+    content_type::Encoding,
+    fetch::{FetchBackend, FetchBody},
+    metadata::normalize_binary_metadata,
+    options::{FetchOptions, Protocol, UserAgent},
+    response_body::{DEFAULT_MAX_MESSAGE_SIZE, DEFAULT_MAX_TRAILER_SIZE},
+    retry::retry,
+};
+
+const GRPC_TIMEOUT: &str = "grpc-timeout";
+const GRPC_STATUS: &str = "grpc-status";
+const GRPC_ACCEPT_ENCODING: &str = "grpc-accept-encoding";
+const GRPC_ENCODING: &str = "grpc-encoding";
+const X_GRPC_WEB: &str = "x-grpc-web";
+const X_USER_AGENT: &str = "x-user-agent";
 
-use crate::{Error, ResponseBody, fetch::fetch, options::FetchOptions};
+/// Default value of `x-user-agent` header
+const USER_AGENT: &str = concat!("grpc-web-rust/", env!("CARGO_PKG_VERSION"));
 
-pub async fn call(
-    mut base_url: String,
+/// Prefix of headers which are a part of gRPC status
+const GRPC_PREFIX: &str = "grpc-";
+
+/// Maximum number of characters of a non-gRPC response body included in the status message
+const MAX_BODY_PREVIEW_LEN: usize = 256;
+
+pub async fn call<F: FetchBackend + ?Sized>(
+    fetch: &F,
+    base_url: String,
     request: Request<Body>,
     options: FetchOptions,
 ) -> Result<Response<ResponseBody>, Error> {
-    base_url.push_str(&request.uri().to_string());
+    let url = join_url(&base_url, request.uri());
 
-    let headers = prepare_headers(request.headers())?;
-    let body = prepare_body(request).await?;
+    let encoding = Encoding::from(options.encoding.unwrap_or_default());
 
-    let request = prepare_request(&base_url, headers, body)?;
-    let (init, abort) = options.request_init()?;
-    let response = fetch(&request, &init).await?;
+    // Use per-call deadline set by `tonic` if it is shorter than client's timeout.
+    let timeout = match (options.timeout, parse_grpc_timeout(request.headers())?) {
+        (Some(timeout), Some(grpc_timeout)) => Some(timeout.min(grpc_timeout)),
+        (timeout, grpc_timeout) => timeout.or(grpc_timeout),
+    };
 
-    let result = Response::builder().status(response.status());
-    let (result, content_type) = set_response_headers(result, &response)?;
+    if let Some(Protocol::Connect(ref connect_options)) = options.protocol {
+        return connect::call(fetch, url, request, &options, connect_options, timeout).await;
+    }
 
-    let content_type = content_type.ok_or(Error::MissingContentTypeHeader)?;
-    let body_stream = response.body().ok_or(Error::MissingResponseBody)?;
+    let headers = prepare_headers(&options, request.headers(), encoding)?;
+
+    if options.request_streaming.unwrap_or_default() {
+        let body = prepare_streaming_body(request, encoding);
+        return fetch_response(fetch, &url, &headers, body, &options, timeout).await;
+    }
 
-    let body = ResponseBody::new(body_stream, &content_type, abort)?;
+    let path = request.uri().path().to_owned();
+    let body = request.collect().await?.to_bytes();
 
-    result.body(body).map_err(Into::into)
+    match options.retry {
+        Some(ref policy) if policy.idempotent_methods.contains(&path) => {
+            let unary = policy.unary_methods.contains(&path);
+            retry(policy, timeout, unary, |timeout| {
+                let body = prepare_body(&body, encoding);
+                fetch_response(fetch, &url, &headers, body, &options, timeout)
+            })
+            .await
+        }
+        _ => {
+            let body = prepare_body(&body, encoding);
+            fetch_response(fetch, &url, &headers, body, &options, timeout).await
+        }
+    }
 }
 
-fn prepare_headers(header_map: &HeaderMap<HeaderValue>) -> Result<Headers, Error> {
-    // Construct default headers.
-    let headers = Headers::new().map_err(Error::js_error)?;
-    headers
-        .append(CONTENT_TYPE.as_str(), "application/grpc-web+proto")
-        .map_err(Error::js_error)?;
-    headers
-        .append(ACCEPT.as_str(), "application/grpc-web+proto")
-        .map_err(Error::js_error)?;
-    headers.append("x-grpc-web", "1").map_err(Error::js_error)?;
-
-    // Apply default headers.
-    for (header_name, header_value) in header_map.iter() {
-        // Allow default headers to be overridden except for `content-type`.
-        if header_name != CONTENT_TYPE {
-            headers
-                .set(header_name.as_str(), header_value.to_str()?)
-                .map_err(Error::js_error)?;
+async fn fetch_response<F: FetchBackend + ?Sized>(
+    fetch: &F,
+    url: &str,
+    headers: &HeaderMap,
+    body: FetchBody,
+    options: &FetchOptions,
+    timeout: Option<Duration>,
+) -> Result<Response<ResponseBody>, Error> {
+    let mut request = Request::builder()
+        .method(Method::POST)
+        .uri(url)
+        .body(body)?;
+    *request.headers_mut() = headers.clone();
+
+    let response = fetch.fetch(request, options, timeout).await?;
+    let (mut parts, body_stream) = response.into_parts();
+
+    normalize_binary_metadata(&mut parts.headers);
+
+    // Servers which fail immediately may send the status in headers without a body (or with an empty body).
+    if parts.headers.contains_key(GRPC_STATUS) {
+        let trailers = trailers_only(&parts.headers);
+        return Ok(Response::from_parts(
+            parts,
+            ResponseBody::from_trailers(trailers),
+        ));
+    }
+
+    if parts.status != StatusCode::OK {
+        let body_stream = body_stream.unwrap_or_else(BodyStream::empty);
+        return Err(http_status_error(parts.status, body_stream).await.into());
+    }
+
+    let content_type = parts
+        .headers
+        .get(CONTENT_TYPE)
+        .ok_or(Error::MissingContentTypeHeader)?
+        .to_str()?;
+
+    let body_stream = body_stream.ok_or(Error::MissingResponseBody)?;
+    let compression = parts
+        .headers
+        .get(GRPC_ENCODING)
+        .is_some_and(|encoding| encoding != "identity");
+
+    let body = ResponseBody::new(body_stream, content_type)?
+        .with_limits(
+            options.max_message_size.unwrap_or(DEFAULT_MAX_MESSAGE_SIZE),
+            options.max_trailer_size.unwrap_or(DEFAULT_MAX_TRAILER_SIZE),
+        )
+        .with_compression(compression);
+
+    Ok(Response::from_parts(parts, body))
+}
+
+/// Prepares headers of a `fetch` request
+///
+/// Headers are applied in the following order, later ones replacing earlier ones with the same name:
+/// 1. `accept` header matching the encoding, `x-user-agent` header (see [`FetchOptions::user_agent`]) and
+///    `grpc-accept-encoding` header (see [`FetchOptions::accept_compressed`])
+/// 2. Default headers from [`FetchOptions::headers`]
+/// 3. Request metadata
+///
+/// Protocol headers (`content-type` and `x-grpc-web`) are always set by the client and can not be overridden.
+pub fn prepare_headers(
+    options: &FetchOptions,
+    header_map: &HeaderMap<HeaderValue>,
+    encoding: Encoding,
+) -> Result<HeaderMap, Error> {
+    let default_headers = options.headers.as_ref();
+
+    let mut headers =
+        HeaderMap::with_capacity(header_map.len() + default_headers.map_or(0, HeaderMap::len) + 4);
+    headers.insert(ACCEPT, HeaderValue::from_static(encoding.content_type()));
+    match options.user_agent.as_ref().unwrap_or(&UserAgent::Default) {
+        UserAgent::Default => {
+            headers.insert(X_USER_AGENT, HeaderValue::from_static(USER_AGENT));
         }
+        UserAgent::Custom(user_agent) => {
+            headers.insert(X_USER_AGENT, HeaderValue::from_str(user_agent)?);
+        }
+        UserAgent::Suppressed => {}
+    }
+    if let Some(ref encodings) = options.accept_compressed
+        && !encodings.is_empty()
+    {
+        let encodings = encodings
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        headers.insert(GRPC_ACCEPT_ENCODING, HeaderValue::from_str(&encodings)?);
     }
 
+    for header_map in default_headers.into_iter().chain([header_map]) {
+        for header_name in header_map.keys() {
+            headers.remove(header_name);
+
+            for header_value in header_map.get_all(header_name) {
+                headers.append(header_name.clone(), header_value.clone());
+            }
+        }
+    }
+
+    // Protocol headers take precedence over default headers and request metadata.
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static(encoding.content_type()),
+    );
+    headers.insert(X_GRPC_WEB, HeaderValue::from_static("1"));
+
     Ok(headers)
 }
 
-async fn prepare_body(request: Request<Body>) -> Result<Option<JsValue>, Error> {
-    let body = Some(request.collect().await?.to_bytes());
-    Ok(body.map(|bytes| Uint8Array::from(bytes.as_ref()).into()))
-}
+/// Parses `grpc-timeout` header as described in [gRPC over HTTP2](https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-HTTP2.md#requests)
+fn parse_grpc_timeout(header_map: &HeaderMap<HeaderValue>) -> Result<Option<Duration>, Error> {
+    let Some(value) = header_map.get(GRPC_TIMEOUT) else {
+        return Ok(None);
+    };
 
-fn prepare_request(
-    url: &str,
-    headers: Headers,
-    body: Option<JsValue>,
-) -> Result<web_sys::Request, Error> {
-    let init = RequestInit::new();
+    let value = value.to_str()?;
+
+    // Value is at most 8 ASCII digits followed by a single character unit.
+    if value.len() < 2 || value.len() > 9 || !value.is_ascii() {
+        return Err(Error::InvalidGrpcTimeout(value.to_owned()));
+    }
+
+    let (amount, unit) = value.split_at(value.len() - 1);
 
-    init.set_method("POST");
-    init.set_headers(headers.as_ref());
-    if let Some(ref body) = body {
-        init.set_body(body);
+    if !amount.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(Error::InvalidGrpcTimeout(value.to_owned()));
     }
-    init.set_credentials(RequestCredentials::SameOrigin);
 
-    web_sys::Request::new_with_str_and_init(url, &init).map_err(Error::js_error)
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| Error::InvalidGrpcTimeout(value.to_owned()))?;
+
+    let timeout = match unit {
+        "H" => Duration::from_secs(amount * 60 * 60),
+        "M" => Duration::from_secs(amount * 60),
+        "S" => Duration::from_secs(amount),
+        "m" => Duration::from_millis(amount),
+        "u" => Duration::from_micros(amount),
+        "n" => Duration::from_nanos(amount),
+        _ => return Err(Error::InvalidGrpcTimeout(value.to_owned())),
+    };
+
+    Ok(Some(timeout))
 }
 
-fn set_response_headers(
-    mut result: Builder,
-    response: &web_sys::Response,
-) -> Result<(Builder, Option<String>), Error> {
-    let headers = response.headers();
+/// Extracts trailers of a [trailers-only](https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-HTTP2.md#responses)
+/// response from its headers
+fn trailers_only(headers: &HeaderMap) -> HeaderMap {
+    let mut trailers = HeaderMap::new();
+
+    for (header_name, header_value) in headers.iter() {
+        if header_name.as_str().starts_with(GRPC_PREFIX) {
+            trailers.append(header_name.clone(), header_value.clone());
+        }
+    }
 
-    let header_iter = js_sys::try_iter(headers.as_ref()).map_err(Error::js_error)?;
+    trailers
+}
+
+fn prepare_body(bytes: &Bytes, encoding: Encoding) -> FetchBody {
+    match encoding {
+        Encoding::Base64 => FetchBody::Full(BASE64_STANDARD.encode(bytes).into()),
+        Encoding::None => FetchBody::Full(bytes.clone()),
+    }
+}
 
-    let mut content_type = None;
+fn prepare_streaming_body(request: Request<Body>, encoding: Encoding) -> FetchBody {
+    let body_stream = request.into_body().into_data_stream();
 
-    if let Some(header_iter) = header_iter {
-        for header in header_iter {
-            let header = header.map_err(Error::js_error)?;
-            let pair: Array = header.into();
+    match encoding {
+        Encoding::Base64 => FetchBody::Streaming(Box::pin(base64_stream(body_stream))),
+        Encoding::None => FetchBody::Streaming(Box::pin(body_stream)),
+    }
+}
 
-            let header_name = pair.get(0).as_string();
-            let header_value = pair.get(1).as_string();
+/// Encodes a stream of bytes as a single base64 string
+///
+/// Only whole 3 byte groups of each chunk are encoded and the remaining bytes are carried over to the next chunk, so
+/// that padding only appears at the end of the stream.
+fn base64_stream(
+    body_stream: impl Stream<Item = Result<Bytes, Status>>,
+) -> impl Stream<Item = Result<Bytes, Status>> {
+    let mut remainder = BytesMut::new();
 
-            match (header_name, header_value) {
-                (Some(header_name), Some(header_value)) => {
-                    if header_name == CONTENT_TYPE.as_str() {
-                        content_type = Some(header_value.clone());
-                    }
+    body_stream
+        .map(Some)
+        .chain(stream::once(ready(None)))
+        .filter_map(move |item| {
+            let encoded = match item {
+                Some(Ok(bytes)) => {
+                    remainder.extend_from_slice(&bytes);
+                    let len = remainder.len() / 3 * 3;
 
-                    result = result.header(header_name, header_value);
+                    (len > 0).then(|| Ok(BASE64_STANDARD.encode(remainder.split_to(len)).into()))
                 }
-                _ => continue,
+                Some(Err(status)) => Some(Err(status)),
+                None if remainder.is_empty() => None,
+                None => Some(Ok(BASE64_STANDARD.encode(remainder.split()).into())),
+            };
+
+            ready(encoded)
+        })
+}
+
+/// Builds a [`Status`] for a response which did not come from a gRPC-web server (e.g. an error page served by a proxy),
+/// as described in [HTTP to gRPC Status Code Mapping](https://github.com/grpc/grpc/blob/master/doc/http-grpc-status-mapping.md).
+pub async fn http_status_error(status: StatusCode, body_stream: BodyStream) -> Status {
+    let code = http_status_to_code(status);
+
+    let mut message = format!("received HTTP status {}", status.as_u16());
+
+    if let Some(reason) = status.canonical_reason() {
+        message.push(' ');
+        message.push_str(reason);
+    }
+
+    let preview = body_preview(body_stream).await;
+    if !preview.is_empty() {
+        message.push_str(": ");
+        message.push_str(&preview);
+    }
+
+    Status::new(code, message)
+}
+
+pub fn http_status_to_code(status: StatusCode) -> Code {
+    match status.as_u16() {
+        400 => Code::Internal,
+        401 => Code::Unauthenticated,
+        403 => Code::PermissionDenied,
+        404 => Code::Unimplemented,
+        429 | 502 | 503 | 504 => Code::Unavailable,
+        _ => Code::Unknown,
+    }
+}
+
+async fn body_preview(mut body_stream: BodyStream) -> String {
+    // Each character takes at most 4 bytes in UTF-8
+    let max_bytes = MAX_BODY_PREVIEW_LEN * 4;
+
+    let mut bytes = BytesMut::new();
+
+    while bytes.len() < max_bytes {
+        match poll_fn(|cx| Pin::new(&mut body_stream).poll_chunk(cx)).await {
+            Some(Ok(chunk)) => chunk.copy_into(&mut bytes),
+            _ => break,
+        }
+    }
+
+    truncate(String::from_utf8_lossy(&bytes).trim(), MAX_BODY_PREVIEW_LEN)
+}
+
+fn truncate(text: &str, max_len: usize) -> String {
+    match text.char_indices().nth(max_len) {
+        Some((index, _)) => format!("{}...", &text[..index]),
+        None => text.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::FutureExt;
+    use http_body_util::Full;
+
+    use super::*;
+    use crate::{
+        mock_fetch::{MockFetch, data_frame, trailer_frame},
+        options::{Encoding as RequestEncoding, RetryPolicy},
+    };
+
+    fn request(message: &'static [u8]) -> Request<Body> {
+        let mut request = Request::new(Body::new(Full::new(Bytes::from_static(message))));
+        *request.uri_mut() = "/echo.Echo/Echo".parse().unwrap();
+        request
+    }
+
+    fn call_mock(
+        fetch: &MockFetch,
+        request: Request<Body>,
+        options: FetchOptions,
+    ) -> Result<Response<ResponseBody>, Error> {
+        call(
+            fetch,
+            "http://localhost:50051".to_string(),
+            request,
+            options,
+        )
+        .now_or_never()
+        .expect("ready call")
+    }
+
+    #[test]
+    fn test_call() {
+        let fetch = MockFetch::grpc_web()
+            .chunk(data_frame(b"world"))
+            .chunk(trailer_frame("grpc-status: 0\r\n"));
+
+        let response = call_mock(&fetch, request(b"hello"), FetchOptions::new()).unwrap();
+        let body = response
+            .into_body()
+            .collect()
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(body.trailers().unwrap()["grpc-status"], "0");
+        assert_eq!(body.to_bytes(), data_frame(b"world"));
+
+        let requests = fetch.requests();
+        let request = &requests[0].request;
+
+        assert_eq!(request.method(), Method::POST);
+        assert_eq!(request.uri(), "http://localhost:50051/echo.Echo/Echo");
+        assert_eq!(
+            request.headers()[CONTENT_TYPE],
+            "application/grpc-web+proto"
+        );
+        assert_eq!(request.headers()[ACCEPT], "application/grpc-web+proto");
+        assert_eq!(request.headers()["x-grpc-web"], "1");
+        assert_eq!(request.body().as_deref(), Some(&b"hello"[..]));
+    }
+
+    #[test]
+    fn test_call_text_encoding() {
+        let fetch = MockFetch::grpc_web();
+        let options = FetchOptions::new().encoding(RequestEncoding::Text);
+
+        let _ = call_mock(&fetch, request(b"hello"), options);
+
+        let requests = fetch.requests();
+        let request = &requests[0].request;
+
+        assert_eq!(
+            request.headers()[CONTENT_TYPE],
+            "application/grpc-web-text+proto"
+        );
+        assert_eq!(request.body().as_deref(), Some(&b"aGVsbG8="[..]));
+    }
+
+    #[test]
+    fn test_call_timeout() {
+        let vals = [
+            (None, None, None),
+            (
+                Some(Duration::from_secs(2)),
+                None,
+                Some(Duration::from_secs(2)),
+            ),
+            (None, Some("1S"), Some(Duration::from_secs(1))),
+            (
+                Some(Duration::from_secs(2)),
+                Some("1S"),
+                Some(Duration::from_secs(1)),
+            ),
+            (
+                Some(Duration::from_secs(2)),
+                Some("3S"),
+                Some(Duration::from_secs(2)),
+            ),
+        ];
+        for (timeout, grpc_timeout, expected) in vals.iter() {
+            let fetch = MockFetch::grpc_web();
+
+            let mut options = FetchOptions::new();
+            options.timeout = *timeout;
+
+            let mut request = request(b"hello");
+            if let Some(grpc_timeout) = grpc_timeout {
+                request
+                    .headers_mut()
+                    .insert(GRPC_TIMEOUT, HeaderValue::from_static(grpc_timeout));
             }
+
+            let _ = call_mock(&fetch, request, options);
+
+            assert_eq!(fetch.requests()[0].timeout, *expected);
+        }
+    }
+
+    #[test]
+    fn test_call_retry() {
+        // Only calls of idempotent methods are retried, and only responses of unary methods among them are read before
+        // the call resolves
+        let vals = [
+            ("/echo.Echo/Echo", true),
+            ("/echo.Echo/EchoStream", false),
+            ("/echo.Echo/Update", false),
+        ];
+
+        for (path, prefetched) in vals {
+            let fetch = MockFetch::grpc_web().chunk(trailer_frame("grpc-status: 14\r\n"));
+            let policy = RetryPolicy::new()
+                .max_attempts(1)
+                .idempotent_method("/echo.Echo/Echo")
+                .idempotent_method("/echo.Echo/EchoStream")
+                .unary_method("/echo.Echo/Echo")
+                .unary_method("/echo.Echo/Update");
+
+            let mut request = request(b"hello");
+            *request.uri_mut() = path.parse().unwrap();
+
+            let response = call_mock(&fetch, request, FetchOptions::new().retry(policy)).unwrap();
+
+            assert_eq!(response.body().trailers_only().is_some(), prefetched);
+            assert_eq!(fetch.requests().len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_call_http_status() {
+        let fetch = MockFetch::new()
+            .status(503)
+            .header("content-type", "text/html")
+            .chunk("<html>Service ")
+            .chunk("Unavailable</html>");
+
+        let error = call_mock(&fetch, request(b"hello"), FetchOptions::new())
+            .err()
+            .expect("error response");
+        let Error::TonicStatusError(status) = error else {
+            panic!("unexpected error: {error:?}");
+        };
+
+        assert_eq!(status.code(), Code::Unavailable);
+        assert_eq!(
+            status.message(),
+            "received HTTP status 503 Service Unavailable: <html>Service Unavailable</html>"
+        );
+    }
+
+    #[test]
+    fn test_base64_stream() {
+        let chunks = ["hello", "wo", "rld", "!"].map(|chunk| Ok(Bytes::from(chunk)));
+
+        let encoded: Vec<_> = base64_stream(stream::iter(chunks))
+            .map(Result::unwrap)
+            .collect()
+            .now_or_never()
+            .unwrap();
+
+        // Chunks are encoded as one continuous base64 string, which is only padded at the end
+        assert_eq!(
+            encoded.concat(),
+            BASE64_STANDARD.encode("helloworld!").as_bytes()
+        );
+        assert!(
+            encoded[..encoded.len() - 1]
+                .iter()
+                .all(|chunk| !chunk.contains(&b'='))
+        );
+    }
+
+    #[test]
+    fn test_call_stream_error() {
+        let fetch = MockFetch::grpc_web()
+            .chunk(data_frame(b"world"))
+            .error(Error::MalformedResponse);
+
+        let mut body = call_mock(&fetch, request(b"hello"), FetchOptions::new())
+            .unwrap()
+            .into_body();
+
+        let frame = body.frame().now_or_never().unwrap().unwrap().unwrap();
+        assert_eq!(frame.into_data().unwrap(), data_frame(b"world"));
+
+        let error = body.frame().now_or_never().unwrap().unwrap().unwrap_err();
+        assert!(matches!(error, Error::MalformedResponse));
+    }
+
+    #[test]
+    fn test_call_trailers_only() {
+        let vals = [
+            MockFetch::grpc_web().header("grpc-status", "16"),
+            MockFetch::grpc_web()
+                .header("grpc-status", "16")
+                .without_body(),
+            MockFetch::new().status(401).header("grpc-status", "16"),
+        ];
+        for fetch in vals {
+            let fetch = fetch.header("grpc-message", "not%20authenticated");
+
+            let response = call_mock(&fetch, request(b"hello"), FetchOptions::new()).unwrap();
+            let body = response
+                .into_body()
+                .collect()
+                .now_or_never()
+                .unwrap()
+                .unwrap();
+            let trailers = body.trailers().unwrap();
+
+            assert_eq!(trailers["grpc-status"], "16");
+            assert_eq!(trailers["grpc-message"], "not%20authenticated");
+            assert!(body.to_bytes().is_empty());
         }
     }
 
-    Ok((result, content_type))
+    #[test]
+    fn test_call_missing_body() {
+        let fetch = MockFetch::grpc_web().without_body();
+
+        let error = call_mock(&fetch, request(b"hello"), FetchOptions::new())
+            .err()
+            .expect("error response");
+
+        assert!(matches!(error, Error::MissingResponseBody));
+    }
+
+    #[test]
+    fn test_call_missing_content_type() {
+        let fetch = MockFetch::new();
+
+        let error = call_mock(&fetch, request(b"hello"), FetchOptions::new())
+            .err()
+            .expect("error response");
+
+        assert!(matches!(error, Error::MissingContentTypeHeader));
+    }
+
+    #[test]
+    fn test_prepare_headers() {
+        let mut default_headers = HeaderMap::new();
+        default_headers.insert("x-api-key", HeaderValue::from_static("key"));
+        default_headers.insert("x-tenant-id", HeaderValue::from_static("default"));
+        default_headers.insert(X_GRPC_WEB, HeaderValue::from_static("0"));
+
+        let mut header_map = HeaderMap::new();
+        header_map.insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        header_map.insert(ACCEPT, HeaderValue::from_static("application/grpc-web"));
+        header_map.insert("x-tenant-id", HeaderValue::from_static("tenant"));
+        header_map.append("x-custom", HeaderValue::from_static("a"));
+        header_map.append("x-custom", HeaderValue::from_static("b"));
+
+        let options = FetchOptions::new().headers(default_headers);
+        let headers = prepare_headers(&options, &header_map, Encoding::None).unwrap();
+
+        assert_eq!(headers[CONTENT_TYPE], "application/grpc-web+proto");
+        assert_eq!(headers[ACCEPT], "application/grpc-web");
+        assert_eq!(headers[X_GRPC_WEB], "1");
+        assert_eq!(headers["x-api-key"], "key");
+        assert_eq!(
+            headers.get_all("x-tenant-id").iter().collect::<Vec<_>>(),
+            ["tenant"]
+        );
+        assert_eq!(
+            headers.get_all("x-custom").iter().collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+    }
+
+    #[cfg(all(feature = "gzip", feature = "zstd"))]
+    #[test]
+    fn test_prepare_headers_accept_compressed() {
+        use tonic::codec::CompressionEncoding;
+
+        let options = FetchOptions::new()
+            .accept_compressed(CompressionEncoding::Gzip)
+            .accept_compressed(CompressionEncoding::Zstd)
+            .accept_compressed(CompressionEncoding::Gzip);
+
+        let headers = prepare_headers(&options, &HeaderMap::new(), Encoding::None).unwrap();
+        assert_eq!(headers[GRPC_ACCEPT_ENCODING], "gzip,zstd");
+
+        // Encodings enabled on the generated client take precedence
+        let mut header_map = HeaderMap::new();
+        header_map.insert(GRPC_ACCEPT_ENCODING, HeaderValue::from_static("gzip"));
+
+        let headers = prepare_headers(&options, &header_map, Encoding::None).unwrap();
+        assert_eq!(headers[GRPC_ACCEPT_ENCODING], "gzip");
+    }
+
+    #[test]
+    fn test_prepare_headers_user_agent() {
+        let vals = [
+            (FetchOptions::new(), Some(USER_AGENT)),
+            (
+                FetchOptions::new().user_agent(UserAgent::Custom("my-app/1.0".to_string())),
+                Some("my-app/1.0"),
+            ),
+            (FetchOptions::new().user_agent(UserAgent::Suppressed), None),
+        ];
+
+        for (options, expected) in vals.iter() {
+            let headers = prepare_headers(options, &HeaderMap::new(), Encoding::None).unwrap();
+
+            assert_eq!(
+                headers
+                    .get(X_USER_AGENT)
+                    .map(|value| value.to_str().unwrap()),
+                *expected
+            );
+        }
+
+        assert!(USER_AGENT.starts_with("grpc-web-rust/"));
+    }
+
+    #[test]
+    fn test_http_status_to_code() {
+        let vals = [
+            (400, Code::Internal),
+            (401, Code::Unauthenticated),
+            (403, Code::PermissionDenied),
+            (404, Code::Unimplemented),
+            (429, Code::Unavailable),
+            (502, Code::Unavailable),
+            (503, Code::Unavailable),
+            (504, Code::Unavailable),
+            (500, Code::Unknown),
+        ];
+        for (status, expected) in vals.iter() {
+            let status = StatusCode::from_u16(*status).unwrap();
+            assert_eq!(http_status_to_code(status), *expected);
+        }
+    }
+
+    #[test]
+    fn test_parse_grpc_timeout() {
+        let vals = [
+            ("2H", Some(Duration::from_secs(7200))),
+            ("3M", Some(Duration::from_secs(180))),
+            ("10S", Some(Duration::from_secs(10))),
+            ("500m", Some(Duration::from_millis(500))),
+            ("250u", Some(Duration::from_micros(250))),
+            ("99999999n", Some(Duration::from_nanos(99999999))),
+            ("", None),
+            ("m", None),
+            ("10", None),
+            ("10s", None),
+            ("-1S", None),
+            ("+1S", None),
+            ("123456789S", None),
+        ];
+        for (value, expected) in vals.iter() {
+            let mut header_map = HeaderMap::new();
+            header_map.insert(GRPC_TIMEOUT, HeaderValue::from_static(value));
+
+            assert_eq!(parse_grpc_timeout(&header_map).ok().flatten(), *expected);
+        }
+
+        assert_eq!(parse_grpc_timeout(&HeaderMap::new()).ok(), Some(None));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("not found", 16), "not found");
+        assert_eq!(truncate("not found", 3), "not...");
+        assert_eq!(truncate("ünïcödé", 3), "ünï...");
+    }
 }
@@ -1,20 +1,31 @@
 use std::{
+    fmt,
     future::Future,
     pin::Pin,
-    task::{Context, Poll},
+    rc::Rc,
+    sync::Arc,
+    task::{Context, Poll, ready},
 };
 
 use http::{Request, Response};
 use tonic::body::Body;
 use tower_service::Service;
 
-use crate::{Error, ResponseBody, call::call, options::FetchOptions};
+use crate::{
+    ClientBuilder, Error, ResponseBody,
+    call::call,
+    fetch::{FetchBackend, JsFetch},
+    limit::{Limiter, Permit},
+    options::{ConcurrencyLimit, FetchOptions},
+};
 
 /// `grpc-web` based transport layer for `tonic` clients
-#[derive(Debug, Clone)]
 pub struct Client {
     base_url: String,
     options: Option<FetchOptions>,
+    backend: Rc<dyn FetchBackend>,
+    limiter: Option<Arc<Limiter>>,
+    permit: Option<Permit>,
 }
 
 impl Client {
@@ -23,6 +34,9 @@ impl Client {
         Self {
             base_url,
             options: None,
+            backend: Rc::new(JsFetch::new()),
+            limiter: None,
+            permit: None,
         }
     }
 
@@ -31,6 +45,28 @@ impl Client {
         Self {
             base_url,
             options: Some(options),
+            backend: Rc::new(JsFetch::new()),
+            limiter: None,
+            permit: None,
+        }
+    }
+
+    /// Creates a new builder for a client
+    pub fn builder(base_url: String) -> ClientBuilder {
+        ClientBuilder::new(base_url)
+    }
+
+    pub(crate) fn from_parts(
+        base_url: String,
+        options: Option<FetchOptions>,
+        backend: Rc<dyn FetchBackend>,
+    ) -> Self {
+        Self {
+            base_url,
+            options,
+            backend,
+            limiter: None,
+            permit: None,
         }
     }
 
@@ -39,8 +75,52 @@ impl Client {
         self.options = Some(options);
         self
     }
+
+    /// Sets the backend used for sending requests (defaults to [`JsFetch`])
+    pub fn with_backend(&mut self, backend: impl FetchBackend + 'static) -> &mut Self {
+        self.backend = Rc::new(backend);
+        self
+    }
+
+    /// Sets limits of concurrent calls made by the client (calls are not limited by default)
+    ///
+    /// Limits are shared by all clones of the client created after calling this method.
+    pub fn with_concurrency_limit(&mut self, concurrency_limit: ConcurrencyLimit) -> &mut Self {
+        self.limiter = Some(Arc::new(Limiter::new(concurrency_limit)));
+        self.permit = None;
+        self
+    }
+}
+
+impl Clone for Client {
+    fn clone(&self) -> Self {
+        // A slot reserved by `poll_ready` belongs to this instance only
+        Self {
+            base_url: self.base_url.clone(),
+            options: self.options.clone(),
+            backend: self.backend.clone(),
+            limiter: self.limiter.clone(),
+            permit: None,
+        }
+    }
 }
 
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("base_url", &self.base_url)
+            .field("options", &self.options)
+            .finish_non_exhaustive()
+    }
+}
+
+// Backends hold JavaScript values which can not be shared between threads. This is only sound on wasm targets without
+// the `atomics` target feature, which always run on a single thread (native builds, e.g. for unit tests, are not).
+#[cfg(all(target_arch = "wasm32", not(target_feature = "atomics")))]
+unsafe impl Send for Client {}
+#[cfg(all(target_arch = "wasm32", not(target_feature = "atomics")))]
+unsafe impl Sync for Client {}
+
 impl Service<Request<Body>> for Client {
     type Response = Response<ResponseBody>;
 
@@ -48,15 +128,175 @@ impl Service<Request<Body>> for Client {
 
     type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
 
-    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if let (Some(limiter), None) = (&self.limiter, &self.permit) {
+            self.permit = Some(ready!(limiter.poll_unary(cx)));
+        }
+
         Poll::Ready(Ok(()))
     }
 
     fn call(&mut self, request: Request<Body>) -> Self::Future {
-        Box::pin(call(
-            self.base_url.clone(),
-            request,
-            self.options.clone().unwrap_or_default(),
-        ))
+        let backend = self.backend.clone();
+        let base_url = self.base_url.clone();
+        let options = self.options.clone().unwrap_or_default();
+        let limiter = self.limiter.clone();
+        let permit = self.permit.take();
+
+        Box::pin(async move {
+            let permit = match limiter {
+                // Streams give up the unary slot reserved by `poll_ready` and wait for a streaming slot instead
+                Some(limiter) if limiter.is_streaming(request.uri().path()) => {
+                    drop(permit);
+                    Some(limiter.streaming().await)
+                }
+                Some(limiter) => match permit {
+                    Some(permit) => Some(permit),
+                    None => Some(limiter.unary().await),
+                },
+                None => None,
+            };
+
+            let response = call(&*backend, base_url, request, options).await?;
+            Ok(response.map(|body| body.with_permit(permit)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use bytes::Bytes;
+    use futures_util::FutureExt;
+    use http_body_util::Full;
+
+    use super::*;
+    use crate::mock_fetch::MockFetch;
+
+    #[test]
+    fn test_with_backend() {
+        let fetch = Rc::new(MockFetch::grpc_web());
+
+        let mut client = Client::new("http://localhost:50051".to_string());
+        client.with_backend(fetch.clone());
+
+        let mut request = Request::new(Body::new(Full::new(Bytes::from_static(b"hello"))));
+        *request.uri_mut() = "/echo.Echo/Echo".parse().unwrap();
+
+        let response = client.call(request).now_or_never().expect("ready call");
+        assert!(response.is_ok());
+
+        let requests = fetch.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            requests[0].request.uri(),
+            "http://localhost:50051/echo.Echo/Echo"
+        );
+    }
+
+    #[test]
+    fn test_concurrency_limit() {
+        fn request(path: &'static str) -> Request<Body> {
+            let mut request = Request::new(Body::new(Full::new(Bytes::from_static(b"hello"))));
+            *request.uri_mut() = path.parse().unwrap();
+            request
+        }
+
+        let mut cx = Context::from_waker(std::task::Waker::noop());
+
+        let mut client = Client::new("http://localhost:50051".to_string());
+        client
+            .with_backend(MockFetch::grpc_web())
+            .with_concurrency_limit(
+                ConcurrencyLimit::new()
+                    .max_unary(1)
+                    .max_streaming(1)
+                    .streaming_method("/echo.Echo/EchoStream"),
+            );
+        let mut other = client.clone();
+        other.with_backend(MockFetch::grpc_web());
+
+        let stream = client
+            .call(request("/echo.Echo/EchoStream"))
+            .now_or_never()
+            .expect("ready call")
+            .unwrap();
+
+        // Streaming budget is exhausted, which does not hold slots of unary calls
+        assert!(client.poll_ready(&mut cx).is_ready());
+        assert!(
+            client
+                .call(request("/echo.Echo/EchoStream"))
+                .now_or_never()
+                .is_none()
+        );
+        assert!(other.poll_ready(&mut cx).is_ready());
+
+        // Unary budget is exhausted until the response body is dropped
+        let response = other
+            .call(request("/echo.Echo/Echo"))
+            .now_or_never()
+            .expect("ready call")
+            .unwrap();
+        assert!(client.poll_ready(&mut cx).is_pending());
+
+        drop(response);
+        assert!(client.poll_ready(&mut cx).is_ready());
+
+        drop(stream);
+        assert!(
+            client
+                .call(request("/echo.Echo/EchoStream"))
+                .now_or_never()
+                .is_some()
+        );
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_send_compressed() {
+        use tonic::{client::Grpc, codec::CompressionEncoding};
+
+        use crate::mock_fetch::{BytesCodec, GrpcClient, data_frame, trailer_frame};
+
+        let fetch = Rc::new(
+            MockFetch::grpc_web()
+                .chunk(data_frame(b"world"))
+                .chunk(trailer_frame("grpc-status: 0\r\n")),
+        );
+
+        let mut client = Client::new("http://localhost:50051".to_string());
+        client.with_backend(fetch.clone());
+
+        let mut grpc = Grpc::new(GrpcClient(client))
+            .send_compressed(CompressionEncoding::Gzip)
+            .accept_compressed(CompressionEncoding::Gzip);
+
+        let response = async {
+            grpc.ready().await.unwrap();
+            grpc.unary(
+                tonic::Request::new(Bytes::from_static(b"hello")),
+                "/echo.Echo/Echo".parse().unwrap(),
+                BytesCodec,
+            )
+            .await
+        }
+        .now_or_never()
+        .expect("ready call")
+        .unwrap();
+
+        assert_eq!(response.into_inner(), &b"world"[..]);
+
+        let requests = fetch.requests();
+        let request = &requests[0].request;
+
+        assert_eq!(request.headers()["grpc-encoding"], "gzip");
+        assert_eq!(request.headers()["grpc-accept-encoding"], "gzip,identity");
+
+        // Compressed flag is set and payload is a gzip stream
+        let body = request.body().as_deref().unwrap();
+        assert_eq!(body[0], 1);
+        assert_eq!(&body[5..7], &[0x1f, 0x8b]);
     }
 }
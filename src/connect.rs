@@ -0,0 +1,957 @@
+//! [Connect protocol](https://connectrpc.com/docs/protocol) support
+//!
+//! `tonic` always produces and expects `grpc` framed messages, so requests are translated into Connect requests and
+//! Connect responses are translated back into `grpc` frames and trailers.
+use std::{future::poll_fn, io::Read, pin::Pin, time::Duration};
+
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use byteorder::{BigEndian, ByteOrder};
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::stream;
+use http::{
+    HeaderMap, HeaderValue, Method, Request, Response, StatusCode,
+    header::{ACCEPT, CONTENT_ENCODING, CONTENT_TYPE, HeaderName},
+};
+use http_body_util::BodyExt;
+use serde::Deserialize;
+use tonic::{Code, Status, body::Body};
+
+use crate::{
+    Error, ResponseBody,
+    body_stream::BodyStream,
+    call::{http_status_error, prepare_headers},
+    content_type::{BASE64, Encoding},
+    fetch::{FetchBackend, FetchBody},
+    metadata::normalize_binary_metadata,
+    options::{ConnectCodec, ConnectOptions, Encoding as RequestEncoding, FetchOptions},
+    response_body::{DEFAULT_MAX_MESSAGE_SIZE, DEFAULT_MAX_TRAILER_SIZE},
+    retry::retry,
+};
+
+const CONNECT_PROTOCOL_VERSION: &str = "connect-protocol-version";
+const CONNECT_TIMEOUT_MS: &str = "connect-timeout-ms";
+const CONNECT_CONTENT_ENCODING: &str = "connect-content-encoding";
+const CONNECT_ACCEPT_ENCODING: &str = "connect-accept-encoding";
+
+const GRPC_ACCEPT_ENCODING: &str = "grpc-accept-encoding";
+const GRPC_ENCODING: &str = "grpc-encoding";
+const GRPC_MESSAGE: &str = "grpc-message";
+const GRPC_STATUS: &str = "grpc-status";
+const GRPC_STATUS_DETAILS_BIN: &str = "grpc-status-details-bin";
+const GRPC_TIMEOUT: &str = "grpc-timeout";
+
+/// Prefix of unary response headers which carry trailers
+const TRAILER_PREFIX: &str = "trailer-";
+
+/// Maximum value of `connect-timeout-ms` header (at most 10 digits)
+const MAX_TIMEOUT_MS: u128 = 9_999_999_999;
+
+/// Length of `grpc` message envelope (flags and message length)
+const ENVELOPE_LEN: usize = 5;
+
+/// Flag of a compressed message envelope
+const COMPRESSED_FLAG: u8 = 0b00000001;
+
+/// Shape of a Connect call
+#[derive(Debug, Clone, Copy)]
+struct CallType {
+    streaming: bool,
+    codec: ConnectCodec,
+}
+
+impl CallType {
+    /// Content type of request and response messages
+    fn content_type(self) -> String {
+        match self.streaming {
+            true => format!("application/connect+{}", self.codec.name()),
+            false => format!("application/{}", self.codec.name()),
+        }
+    }
+}
+
+/// Calls a method using Connect protocol
+pub async fn call<F: FetchBackend + ?Sized>(
+    fetch: &F,
+    url: String,
+    request: Request<Body>,
+    options: &FetchOptions,
+    connect: &ConnectOptions,
+    timeout: Option<Duration>,
+) -> Result<Response<ResponseBody>, Error> {
+    if let Some(RequestEncoding::Text) = options.encoding {
+        return Err(Status::unimplemented(
+            "grpc-web-text encoding is not supported by Connect protocol",
+        )
+        .into());
+    }
+
+    let path = request.uri().path();
+    let request_streaming = options.request_streaming.unwrap_or_default();
+    let streaming = request_streaming || connect.streaming_methods.contains(path);
+    let get = !streaming && connect.get_methods.contains(path);
+    let retry_policy = options
+        .retry
+        .as_ref()
+        .filter(|policy| policy.idempotent_methods.contains(path));
+
+    let call_type = CallType {
+        streaming,
+        codec: connect.codec,
+    };
+
+    let mut headers = prepare_connect_headers(options, request.headers(), call_type)?;
+    if let Some(timeout) = timeout {
+        let timeout_ms = timeout.as_millis().min(MAX_TIMEOUT_MS);
+        headers.insert(CONNECT_TIMEOUT_MS, HeaderValue::from(timeout_ms as u64));
+    }
+
+    let encoding = request.headers().get(GRPC_ENCODING).cloned();
+
+    if request_streaming {
+        // Streaming requests use the same envelopes as `grpc`.
+        let body = FetchBody::Streaming(Box::pin(request.into_body().into_data_stream()));
+        return fetch_response(
+            fetch,
+            Method::POST,
+            &url,
+            &headers,
+            body,
+            options,
+            call_type,
+            timeout,
+        )
+        .await;
+    }
+
+    let body = request.collect().await?.to_bytes();
+
+    let (method, url, body) = if streaming {
+        (Method::POST, url, body)
+    } else {
+        let (compressed, message) = unwrap_envelope(body)?;
+        let encoding = match (compressed, encoding) {
+            (false, _) => None,
+            (true, Some(encoding)) => Some(encoding),
+            (true, None) => {
+                return Err(Status::internal("compressed message without grpc-encoding").into());
+            }
+        };
+
+        if get {
+            headers.remove(CONTENT_TYPE);
+            let url = get_url(url, &message, connect, encoding.as_ref())?;
+            (Method::GET, url, Bytes::new())
+        } else {
+            if let Some(encoding) = encoding {
+                headers.insert(CONTENT_ENCODING, encoding);
+            }
+            (Method::POST, url, message)
+        }
+    };
+
+    let prepare_body = || match method {
+        Method::GET => FetchBody::Empty,
+        _ => FetchBody::Full(body.clone()),
+    };
+
+    match retry_policy {
+        Some(policy) => {
+            // Streaming methods are registered in Connect, so all other calls are known to be unary
+            retry(policy, timeout, !streaming, |timeout| {
+                fetch_response(
+                    fetch,
+                    method.clone(),
+                    &url,
+                    &headers,
+                    prepare_body(),
+                    options,
+                    call_type,
+                    timeout,
+                )
+            })
+            .await
+        }
+        None => {
+            fetch_response(
+                fetch,
+                method.clone(),
+                &url,
+                &headers,
+                prepare_body(),
+                options,
+                call_type,
+                timeout,
+            )
+            .await
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn fetch_response<F: FetchBackend + ?Sized>(
+    fetch: &F,
+    method: Method,
+    url: &str,
+    headers: &HeaderMap,
+    body: FetchBody,
+    options: &FetchOptions,
+    call_type: CallType,
+    timeout: Option<Duration>,
+) -> Result<Response<ResponseBody>, Error> {
+    let mut request = Request::builder().method(method).uri(url).body(body)?;
+    *request.headers_mut() = headers.clone();
+
+    let response = fetch.fetch(request, options, timeout).await?;
+    let (mut parts, body_stream) = response.into_parts();
+    let body_stream = body_stream.unwrap_or_else(BodyStream::empty);
+
+    let max_message_size = options.max_message_size.unwrap_or(DEFAULT_MAX_MESSAGE_SIZE);
+    let max_trailer_size = options.max_trailer_size.unwrap_or(DEFAULT_MAX_TRAILER_SIZE);
+
+    if parts.status != StatusCode::OK {
+        // Connect reports errors of unary calls (and streaming calls which fail before sending a response) as JSON, which
+        // may be as large as a message when it contains error details
+        let body = read_body(body_stream, max_message_size).await?;
+
+        let Some(Ok(error)) = body.as_deref().map(serde_json::from_slice::<ConnectError>) else {
+            let body_stream = match body {
+                Some(body) => BodyStream::from_stream(stream::iter([Ok(body)])),
+                None => BodyStream::empty(),
+            };
+            return Err(http_status_error(parts.status, body_stream).await.into());
+        };
+
+        let mut trailers = take_unary_trailers(&mut parts.headers);
+        error.append_to(&mut trailers)?;
+        normalize_binary_metadata(&mut trailers);
+
+        // Error is reported as a trailers-only response with the status in headers
+        for (header_name, header_value) in trailers.iter() {
+            parts
+                .headers
+                .append(header_name.clone(), header_value.clone());
+        }
+        normalize_binary_metadata(&mut parts.headers);
+
+        return Ok(Response::from_parts(
+            parts,
+            ResponseBody::from_trailers(trailers),
+        ));
+    }
+
+    let content_type = parts
+        .headers
+        .get(CONTENT_TYPE)
+        .ok_or(Error::MissingContentTypeHeader)?
+        .to_str()?;
+
+    let expected_content_type = call_type.content_type();
+    if content_type.split(';').next().map(str::trim) != Some(expected_content_type.as_str()) {
+        return Err(Error::InvalidContentType(content_type.to_owned()));
+    }
+
+    if call_type.streaming {
+        // Compressed messages are decompressed by `tonic` based on `grpc-encoding` header
+        let encoding = parts.headers.remove(CONNECT_CONTENT_ENCODING);
+        let compression = encoding
+            .as_ref()
+            .is_some_and(|encoding| encoding != "identity");
+        if let Some(encoding) = encoding {
+            parts.headers.insert(GRPC_ENCODING, encoding);
+        }
+
+        normalize_binary_metadata(&mut parts.headers);
+
+        let body = ResponseBody::new(body_stream, Encoding::None.content_type())?
+            .with_limits(max_message_size, max_trailer_size)
+            .with_compression(compression)
+            .with_connect(parts.headers.get(GRPC_ENCODING).cloned());
+
+        return Ok(Response::from_parts(parts, body));
+    }
+
+    // Unary response bodies are compressed using HTTP compression, which is handled by the browser
+    let message = read_body(body_stream, max_message_size)
+        .await?
+        .ok_or_else(|| {
+            Status::resource_exhausted(format!(
+                "message length too large: the limit is {max_message_size} bytes"
+            ))
+        })?;
+
+    let mut trailers = take_unary_trailers(&mut parts.headers);
+    trailers.insert(GRPC_STATUS, HeaderValue::from_static("0"));
+    normalize_binary_metadata(&mut trailers);
+    normalize_binary_metadata(&mut parts.headers);
+
+    let mut frame = BytesMut::with_capacity(message.len() + ENVELOPE_LEN);
+    frame.put_u8(0);
+    frame.put_u32(message.len() as u32);
+    frame.put(message);
+
+    Ok(Response::from_parts(
+        parts,
+        ResponseBody::from_message(frame, trailers),
+    ))
+}
+
+/// Prepares headers of a Connect request from headers of a `grpc-web` request
+fn prepare_connect_headers(
+    options: &FetchOptions,
+    header_map: &HeaderMap,
+    call_type: CallType,
+) -> Result<HeaderMap, Error> {
+    let mut headers = prepare_headers(options, header_map, Encoding::None)?;
+
+    headers.remove(ACCEPT);
+    headers.remove("x-grpc-web");
+    headers.remove("te");
+    headers.remove(GRPC_TIMEOUT);
+
+    let encoding = headers.remove(GRPC_ENCODING);
+    let accept_encoding = headers.remove(GRPC_ACCEPT_ENCODING);
+
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_str(&call_type.content_type())?,
+    );
+
+    // Unary calls use HTTP compression for responses, which is negotiated by the browser
+    if call_type.streaming {
+        if let Some(encoding) = encoding {
+            headers.insert(CONNECT_CONTENT_ENCODING, encoding);
+        }
+        if let Some(accept_encoding) = accept_encoding {
+            headers.insert(CONNECT_ACCEPT_ENCODING, accept_encoding);
+        }
+    }
+
+    headers.insert(CONNECT_PROTOCOL_VERSION, HeaderValue::from_static("1"));
+
+    Ok(headers)
+}
+
+/// Removes the envelope of a unary request message and returns whether the message is compressed
+fn unwrap_envelope(mut body: Bytes) -> Result<(bool, Bytes), Error> {
+    let invalid = || Status::internal("unary request must contain exactly one message");
+
+    if body.len() < ENVELOPE_LEN {
+        return Err(invalid().into());
+    }
+
+    let flags = body[0];
+    let length = BigEndian::read_u32(&body[1..ENVELOPE_LEN]) as usize;
+
+    if body.len() - ENVELOPE_LEN != length {
+        return Err(invalid().into());
+    }
+
+    Ok((flags & COMPRESSED_FLAG != 0, body.split_off(ENVELOPE_LEN)))
+}
+
+/// Builds the URL of a unary `GET` request
+fn get_url(
+    mut url: String,
+    message: &[u8],
+    connect: &ConnectOptions,
+    encoding: Option<&HeaderValue>,
+) -> Result<String, Error> {
+    url.push_str("?connect=v1&encoding=");
+    url.push_str(connect.codec.name());
+    url.push_str("&base64=1&message=");
+    url.push_str(&BASE64_URL_SAFE_NO_PAD.encode(message));
+
+    if let Some(encoding) = encoding {
+        url.push_str("&compression=");
+        url.push_str(encoding.to_str()?);
+    }
+
+    Ok(url)
+}
+
+/// Removes headers prefixed with `trailer-` from unary response headers and returns them as trailers
+fn take_unary_trailers(headers: &mut HeaderMap) -> HeaderMap {
+    let mut trailers = HeaderMap::new();
+    let mut remaining = HeaderMap::with_capacity(headers.len());
+
+    // `HeaderMap::into_iter` yields the name only with the first value of each header
+    let mut current = None;
+
+    for (header_name, header_value) in std::mem::take(headers) {
+        if let Some(header_name) = header_name {
+            let trailer_name = header_name
+                .as_str()
+                .strip_prefix(TRAILER_PREFIX)
+                .and_then(|name| HeaderName::from_bytes(name.as_bytes()).ok());
+
+            current = Some(match trailer_name {
+                Some(trailer_name) => (true, trailer_name),
+                None => (false, header_name),
+            });
+        }
+
+        match current {
+            Some((true, ref header_name)) => trailers.append(header_name.clone(), header_value),
+            Some((false, ref header_name)) => remaining.append(header_name.clone(), header_value),
+            None => continue,
+        };
+    }
+
+    *headers = remaining;
+    trailers
+}
+
+/// Reads a response body up to `max_len` bytes
+///
+/// Returns `None` if the body is longer than `max_len`.
+async fn read_body(mut body_stream: BodyStream, max_len: usize) -> Result<Option<Bytes>, Error> {
+    let mut bytes = BytesMut::new();
+
+    while let Some(chunk) = poll_fn(|cx| Pin::new(&mut body_stream).poll_chunk(cx)).await {
+        chunk?.copy_into(&mut bytes);
+
+        if bytes.len() > max_len {
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(bytes.freeze()))
+}
+
+/// Decompresses an end-of-stream message compressed using `encoding` (value of `connect-content-encoding` header of
+/// the response), reading at most `limit` bytes of decompressed output
+#[cfg_attr(
+    not(any(feature = "gzip", feature = "deflate", feature = "zstd")),
+    allow(unreachable_code, unused_variables)
+)]
+pub fn decompress_end_stream(
+    encoding: Option<&HeaderValue>,
+    bytes: &[u8],
+    limit: usize,
+) -> Result<Vec<u8>, Error> {
+    let encoding = encoding.map(HeaderValue::to_str).transpose()?;
+
+    let reader: Box<dyn Read + '_> = match encoding {
+        #[cfg(feature = "gzip")]
+        Some("gzip") => Box::new(flate2::read::GzDecoder::new(bytes)),
+        #[cfg(feature = "deflate")]
+        Some("deflate") => Box::new(flate2::read::ZlibDecoder::new(bytes)),
+        #[cfg(feature = "zstd")]
+        Some("zstd") => {
+            Box::new(zstd::stream::read::Decoder::new(bytes).map_err(|_| Error::MalformedResponse)?)
+        }
+        encoding => {
+            return Err(Status::internal(format!(
+                "received compressed end-of-stream message with unsupported encoding: {}",
+                encoding.unwrap_or("identity")
+            ))
+            .into());
+        }
+    };
+
+    let mut decompressed = Vec::new();
+    reader
+        .take(limit as u64)
+        .read_to_end(&mut decompressed)
+        .map_err(|_| Error::MalformedResponse)?;
+
+    Ok(decompressed)
+}
+
+/// Parses Connect's end-of-stream message into trailers
+pub fn parse_end_stream(bytes: &[u8]) -> Result<HeaderMap, Error> {
+    let end_stream =
+        serde_json::from_slice::<EndStream>(bytes).map_err(|_| Error::MalformedResponse)?;
+
+    let mut trailers = HeaderMap::new();
+
+    for (key, values) in end_stream.metadata {
+        let Ok(header_name) = HeaderName::from_bytes(key.as_bytes()) else {
+            continue;
+        };
+
+        for value in values {
+            trailers.append(header_name.clone(), HeaderValue::from_str(&value)?);
+        }
+    }
+
+    match end_stream.error {
+        Some(error) => error.append_to(&mut trailers)?,
+        None => {
+            trailers.insert(GRPC_STATUS, HeaderValue::from_static("0"));
+        }
+    }
+
+    Ok(trailers)
+}
+
+/// Connect's end-of-stream message
+#[derive(Debug, Deserialize)]
+struct EndStream {
+    #[serde(default)]
+    error: Option<ConnectError>,
+    #[serde(default)]
+    metadata: std::collections::HashMap<String, Vec<String>>,
+}
+
+/// Connect's error
+#[derive(Debug, Deserialize)]
+struct ConnectError {
+    code: String,
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    details: Vec<ErrorDetail>,
+}
+
+/// Detail of a Connect error (a protobuf `Any` message)
+#[derive(Debug, Deserialize)]
+struct ErrorDetail {
+    #[serde(rename = "type")]
+    type_name: String,
+    value: String,
+}
+
+impl ConnectError {
+    /// Appends the error as `grpc` status trailers
+    fn append_to(self, trailers: &mut HeaderMap) -> Result<(), Error> {
+        let code = code_from_str(&self.code);
+
+        trailers.insert(GRPC_STATUS, HeaderValue::from(code as i32));
+        if !self.message.is_empty() {
+            trailers.insert(
+                GRPC_MESSAGE,
+                HeaderValue::from_str(&percent_encode(&self.message))?,
+            );
+        }
+
+        if !self.details.is_empty() {
+            let details = encode_status(code, &self.message, &self.details)?;
+            trailers.insert(
+                GRPC_STATUS_DETAILS_BIN,
+                HeaderValue::from_str(&BASE64.encode(details))?,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Maps a Connect error code to `grpc` status code
+fn code_from_str(code: &str) -> Code {
+    match code {
+        "canceled" => Code::Cancelled,
+        "unknown" => Code::Unknown,
+        "invalid_argument" => Code::InvalidArgument,
+        "deadline_exceeded" => Code::DeadlineExceeded,
+        "not_found" => Code::NotFound,
+        "already_exists" => Code::AlreadyExists,
+        "permission_denied" => Code::PermissionDenied,
+        "resource_exhausted" => Code::ResourceExhausted,
+        "failed_precondition" => Code::FailedPrecondition,
+        "aborted" => Code::Aborted,
+        "out_of_range" => Code::OutOfRange,
+        "unimplemented" => Code::Unimplemented,
+        "internal" => Code::Internal,
+        "unavailable" => Code::Unavailable,
+        "data_loss" => Code::DataLoss,
+        "unauthenticated" => Code::Unauthenticated,
+        _ => Code::Unknown,
+    }
+}
+
+/// Percent encodes a status message as described in
+/// [gRPC over HTTP2](https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-HTTP2.md#responses)
+fn percent_encode(message: &str) -> String {
+    let mut encoded = String::with_capacity(message.len());
+
+    for byte in message.bytes() {
+        match byte {
+            b' '..=b'~' if byte != b'%' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    encoded
+}
+
+/// Encodes a `google.rpc.Status` message which is sent in `grpc-status-details-bin` trailer
+fn encode_status(code: Code, message: &str, details: &[ErrorDetail]) -> Result<Vec<u8>, Error> {
+    let mut status = Vec::new();
+
+    if code != Code::Ok {
+        put_tag(&mut status, 1, 0);
+        put_varint(&mut status, code as i32 as u64);
+    }
+    put_bytes(&mut status, 2, message.as_bytes());
+
+    for detail in details {
+        let value = BASE64.decode(detail.value.trim_end_matches('='))?;
+
+        let mut any = Vec::new();
+        put_bytes(
+            &mut any,
+            1,
+            format!("type.googleapis.com/{}", detail.type_name).as_bytes(),
+        );
+        put_bytes(&mut any, 2, &value);
+
+        put_bytes(&mut status, 3, &any);
+    }
+
+    Ok(status)
+}
+
+fn put_tag(buf: &mut Vec<u8>, field: u64, wire_type: u64) {
+    put_varint(buf, (field << 3) | wire_type);
+}
+
+fn put_bytes(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    if bytes.is_empty() {
+        return;
+    }
+
+    put_tag(buf, field, 2);
+    put_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::FutureExt;
+    use http_body_util::Full;
+
+    use super::*;
+    use crate::{
+        call::call as call_client,
+        mock_fetch::{MockFetch, data_frame},
+        options::Protocol,
+    };
+
+    fn request(message: &'static [u8]) -> Request<Body> {
+        let mut request = Request::new(Body::new(Full::new(data_frame(message))));
+        *request.uri_mut() = "/echo.Echo/Echo".parse().unwrap();
+        request
+    }
+
+    fn call_mock(
+        fetch: &MockFetch,
+        request: Request<Body>,
+        connect: ConnectOptions,
+    ) -> Result<Response<ResponseBody>, Error> {
+        let options = FetchOptions::new()
+            .protocol(Protocol::Connect(connect))
+            .timeout(Duration::from_secs(2));
+
+        call_client(
+            fetch,
+            "http://localhost:50051".to_string(),
+            request,
+            options,
+        )
+        .now_or_never()
+        .expect("ready call")
+    }
+
+    fn collect(response: Response<ResponseBody>) -> (Bytes, HeaderMap) {
+        let body = response
+            .into_body()
+            .collect()
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        let trailers = body.trailers().cloned().unwrap_or_default();
+        (body.to_bytes(), trailers)
+    }
+
+    fn end_stream_frame(json: &str) -> Bytes {
+        let mut buf = BytesMut::new();
+        buf.put_u8(0b00000010);
+        buf.put_u32(json.len() as u32);
+        buf.put_slice(json.as_bytes());
+        buf.freeze()
+    }
+
+    #[test]
+    fn test_unary() {
+        let fetch = MockFetch::new()
+            .header("content-type", "application/proto")
+            .header("x-session-id", "session-1")
+            .header("trailer-x-trailer", "a")
+            .header("trailer-x-trailer", "b")
+            .chunk(Bytes::from_static(b"wor"))
+            .chunk(Bytes::from_static(b"ld"));
+
+        let response = call_mock(&fetch, request(b"hello"), ConnectOptions::new()).unwrap();
+
+        assert_eq!(response.headers()["x-session-id"], "session-1");
+        assert!(!response.headers().contains_key("trailer-x-trailer"));
+
+        let (data, trailers) = collect(response);
+        assert_eq!(data, data_frame(b"world"));
+        assert_eq!(trailers[GRPC_STATUS], "0");
+        assert_eq!(
+            trailers.get_all("x-trailer").iter().collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+
+        let requests = fetch.requests();
+        let request = &requests[0].request;
+
+        assert_eq!(request.method(), Method::POST);
+        assert_eq!(request.uri(), "http://localhost:50051/echo.Echo/Echo");
+        assert_eq!(request.headers()[CONTENT_TYPE], "application/proto");
+        assert_eq!(request.headers()[CONNECT_PROTOCOL_VERSION], "1");
+        assert_eq!(request.headers()[CONNECT_TIMEOUT_MS], "2000");
+        assert!(!request.headers().contains_key("x-grpc-web"));
+        assert_eq!(request.body().as_deref(), Some(&b"hello"[..]));
+    }
+
+    #[test]
+    fn test_text_encoding() {
+        let fetch = MockFetch::new().header("content-type", "application/proto");
+        let options = FetchOptions::new()
+            .protocol(Protocol::Connect(ConnectOptions::new()))
+            .encoding(RequestEncoding::Text);
+
+        let error = call_client(
+            &fetch,
+            "http://localhost:50051".to_string(),
+            request(b"hello"),
+            options,
+        )
+        .now_or_never()
+        .expect("ready call")
+        .err()
+        .expect("error response");
+
+        assert!(
+            matches!(error, Error::TonicStatusError(ref status) if status.code() == Code::Unimplemented)
+        );
+        assert!(fetch.requests().is_empty());
+    }
+
+    #[test]
+    fn test_unary_get() {
+        let fetch = MockFetch::new().header("content-type", "application/json");
+        let connect = ConnectOptions::new()
+            .codec(ConnectCodec::Json)
+            .get_method("/echo.Echo/Echo");
+
+        let _ = call_mock(&fetch, request(b"{\"message\":\"hi\"}"), connect);
+
+        let requests = fetch.requests();
+        let request = &requests[0].request;
+
+        assert_eq!(request.method(), Method::GET);
+        assert_eq!(
+            request.uri(),
+            "http://localhost:50051/echo.Echo/Echo?connect=v1&encoding=json&base64=1&message=eyJtZXNzYWdlIjoiaGkifQ"
+        );
+        assert!(!request.headers().contains_key(CONTENT_TYPE));
+        assert_eq!(request.body(), &None);
+    }
+
+    #[test]
+    fn test_unary_error() {
+        let fetch = MockFetch::new()
+            .status(404)
+            .header("content-type", "application/json")
+            .header("trailer-x-trailer", "a")
+            .chunk(Bytes::from_static(
+                br#"{"code":"not_found","message":"100% missing","details":[{"type":"google.rpc.ErrorInfo","value":"CgNmb28"}]}"#,
+            ));
+
+        let response = call_mock(&fetch, request(b"hello"), ConnectOptions::new()).unwrap();
+
+        assert_eq!(response.headers()[GRPC_MESSAGE], "100%25 missing");
+
+        let status = Status::from_header_map(response.headers()).expect("status");
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(status.message(), "100% missing");
+        assert_eq!(
+            status.details(),
+            encode_status(
+                Code::NotFound,
+                "100% missing",
+                &[ErrorDetail {
+                    type_name: "google.rpc.ErrorInfo".to_string(),
+                    value: "CgNmb28".to_string(),
+                }]
+            )
+            .unwrap()
+        );
+
+        let (data, trailers) = collect(response);
+        assert!(data.is_empty());
+        assert_eq!(trailers[GRPC_STATUS], "5");
+        assert_eq!(trailers["x-trailer"], "a");
+    }
+
+    #[test]
+    fn test_unary_error_size() {
+        // Error details may be larger than the limit of trailers
+        let message = "x".repeat(DEFAULT_MAX_TRAILER_SIZE);
+        let fetch = MockFetch::new()
+            .status(404)
+            .header("content-type", "application/json")
+            .chunk(format!(r#"{{"code":"not_found","message":"{message}"}}"#));
+
+        let response = call_mock(&fetch, request(b"hello"), ConnectOptions::new()).unwrap();
+
+        let status = Status::from_header_map(response.headers()).expect("status");
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(status.message(), message);
+    }
+
+    #[test]
+    fn test_unary_http_error() {
+        let fetch = MockFetch::new()
+            .status(503)
+            .header("content-type", "text/plain")
+            .chunk(Bytes::from_static(b"upstream unavailable"));
+
+        let status = match call_mock(&fetch, request(b"hello"), ConnectOptions::new()) {
+            Err(Error::TonicStatusError(status)) => status,
+            result => panic!("unexpected result: {:?}", result.err()),
+        };
+
+        assert_eq!(status.code(), Code::Unavailable);
+        assert!(status.message().ends_with("upstream unavailable"));
+    }
+
+    #[test]
+    fn test_streaming() {
+        let vals = [
+            (
+                r#"{"metadata":{"x-trailer":["a","b"]}}"#,
+                "0",
+                None,
+                vec!["a", "b"],
+            ),
+            (
+                r#"{"error":{"code":"unavailable","message":"try again"}}"#,
+                "14",
+                Some("try again"),
+                vec![],
+            ),
+        ];
+
+        for (end_stream, status, message, metadata) in vals {
+            let fetch = MockFetch::new()
+                .header("content-type", "application/connect+proto")
+                .chunk(data_frame(b"a"))
+                .chunk(data_frame(b"b"))
+                .chunk(end_stream_frame(end_stream));
+            let connect = ConnectOptions::new().streaming_method("/echo.Echo/Echo");
+
+            let response = call_mock(&fetch, request(b"hello"), connect).unwrap();
+            let (data, trailers) = collect(response);
+
+            assert_eq!(data, [data_frame(b"a"), data_frame(b"b")].concat());
+            assert_eq!(trailers[GRPC_STATUS], status);
+            assert_eq!(
+                trailers
+                    .get(GRPC_MESSAGE)
+                    .map(|value| value.to_str().unwrap()),
+                message
+            );
+            assert_eq!(
+                trailers.get_all("x-trailer").iter().collect::<Vec<_>>(),
+                metadata
+            );
+
+            let requests = fetch.requests();
+            let request = &requests[0].request;
+
+            assert_eq!(request.headers()[CONTENT_TYPE], "application/connect+proto");
+            assert_eq!(request.body().as_deref(), Some(&data_frame(b"hello")[..]));
+        }
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_streaming_compressed_end_stream() {
+        use std::io::Write;
+
+        use flate2::{Compression, write::GzEncoder};
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(br#"{"error":{"code":"unavailable","message":"try again"}}"#)
+            .unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut end_stream = BytesMut::new();
+        end_stream.put_u8(0b00000011);
+        end_stream.put_u32(compressed.len() as u32);
+        end_stream.put_slice(&compressed);
+
+        let fetch = MockFetch::new()
+            .header("content-type", "application/connect+proto")
+            .header("connect-content-encoding", "gzip")
+            .chunk(data_frame(b"a"))
+            .chunk(end_stream.freeze());
+        let connect = ConnectOptions::new().streaming_method("/echo.Echo/Echo");
+
+        let response = call_mock(&fetch, request(b"hello"), connect).unwrap();
+        assert_eq!(response.headers()[GRPC_ENCODING], "gzip");
+
+        let (data, trailers) = collect(response);
+        assert_eq!(data, data_frame(b"a"));
+        assert_eq!(trailers[GRPC_STATUS], "14");
+        assert_eq!(trailers[GRPC_MESSAGE], "try again");
+    }
+
+    #[test]
+    fn test_invalid_content_type() {
+        let fetch = MockFetch::grpc_web().chunk(data_frame(b"world"));
+        let result = call_mock(&fetch, request(b"hello"), ConnectOptions::new());
+
+        assert!(matches!(result, Err(Error::InvalidContentType(_))));
+    }
+
+    #[test]
+    fn test_unwrap_envelope() {
+        let vals = [
+            (data_frame(b"hello"), Some((false, &b"hello"[..]))),
+            (data_frame(b""), Some((false, &b""[..]))),
+            ([data_frame(b"a"), data_frame(b"b")].concat().into(), None),
+            (Bytes::from_static(&[0, 0, 0]), None),
+        ];
+
+        for (body, expected) in vals {
+            match (unwrap_envelope(body), expected) {
+                (Ok((compressed, message)), Some((expected_compressed, expected_message))) => {
+                    assert_eq!(compressed, expected_compressed);
+                    assert_eq!(message, expected_message);
+                }
+                (Err(Error::TonicStatusError(status)), None) => {
+                    assert_eq!(status.code(), Code::Internal)
+                }
+                (result, _) => panic!("unexpected result: {:?}", result.err()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_percent_encode() {
+        let vals = [
+            ("ok", "ok"),
+            ("100% done", "100%25 done"),
+            ("línea\n", "l%C3%ADnea%0A"),
+        ];
+
+        for (message, expected) in vals.iter() {
+            assert_eq!(percent_encode(message), *expected);
+        }
+    }
+}
@@ -1,10 +1,23 @@
-use crate::Error;
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+};
+
+use crate::{Error, options};
 
 const GRPC_WEB: &str = "application/grpc-web";
 const GRPC_WEB_PROTO: &str = "application/grpc-web+proto";
 const GRPC_WEB_TEXT: &str = "application/grpc-web-text";
 const GRPC_WEB_TEXT_PROTO: &str = "application/grpc-web-text+proto";
 
+/// Base64 engine which accepts both padded and unpadded input and encodes without padding
+pub const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new()
+        .with_encode_padding(false)
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Encoding {
     Base64,
@@ -22,6 +35,22 @@ impl Encoding {
         }
         Err(Error::InvalidContentType(content_type.to_owned()))
     }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Encoding::Base64 => GRPC_WEB_TEXT_PROTO,
+            Encoding::None => GRPC_WEB_PROTO,
+        }
+    }
+}
+
+impl From<options::Encoding> for Encoding {
+    fn from(value: options::Encoding) -> Self {
+        match value {
+            options::Encoding::Binary => Encoding::None,
+            options::Encoding::Text => Encoding::Base64,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -46,4 +75,14 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_content_type_round_trip() {
+        for encoding in [Encoding::Base64, Encoding::None] {
+            assert_eq!(
+                Encoding::from_content_type(encoding.content_type()).ok(),
+                Some(encoding)
+            );
+        }
+    }
 }
@@ -1,11 +1,24 @@
+use std::fmt;
+
 use http::header::{InvalidHeaderName, InvalidHeaderValue, ToStrError};
-use js_sys::Object;
+use js_sys::{Object, TypeError};
 use thiserror::Error;
+use tonic::Status;
 use wasm_bindgen::{JsCast, JsValue};
+use web_sys::DomException;
+
+/// Name of `DOMException` used as abort reason when a request times out
+pub(crate) const TIMEOUT_ERROR: &str = "TimeoutError";
+
+/// Name of `DOMException` thrown when a request is aborted
+const ABORT_ERROR: &str = "AbortError";
 
 /// Error type for `tonic-web-wasm-client`
 #[derive(Debug, Error)]
 pub enum Error {
+    /// Request was aborted
+    #[error("request aborted: {0}")]
+    Aborted(#[source] JsErrorValue),
     /// Base64 decode error
     #[error("base64 decode error")]
     Base64DecodeError(#[from] base64::DecodeError),
@@ -18,9 +31,15 @@ pub enum Error {
     /// HTTP error
     #[error("HTTP error")]
     HttpError(#[from] http::Error),
+    /// Invalid base URL
+    #[error("invalid base URL {0}")]
+    InvalidBaseUrl(String),
     /// Invalid content type
     #[error("invalid content type: {0}")]
     InvalidContentType(String),
+    /// Invalid `grpc-timeout` header
+    #[error("invalid grpc-timeout header: {0}")]
+    InvalidGrpcTimeout(String),
     /// Invalid header name
     #[error("invalid header name")]
     InvalidHeaderName(#[from] InvalidHeaderName),
@@ -39,6 +58,18 @@ pub enum Error {
     /// Missing response body in HTTP call
     #[error("missing response body in HTTP call")]
     MissingResponseBody,
+    /// Network error (e.g. connection failure or CORS rejection)
+    #[error("network error: {0}")]
+    NetworkError(#[source] JsErrorValue),
+    /// Streaming request bodies are not supported by the browser
+    #[error("streaming request bodies are not supported by the browser")]
+    RequestStreamingNotSupported,
+    /// Error while reading response body stream
+    #[error("failed to read response body: {0}")]
+    StreamReadError(#[source] JsErrorValue),
+    /// Request timed out
+    #[error("request timed out")]
+    TimedOut(#[source] JsErrorValue),
     /// gRPC error
     #[error("gRPC error")]
     TonicStatusError(#[from] tonic::Status),
@@ -47,14 +78,166 @@ pub enum Error {
 impl Error {
     /// Initialize js error from js value
     pub(crate) fn js_error(value: JsValue) -> Self {
+        Self::JsError(js_object_display(&value))
+    }
+
+    /// Initialize error from a rejected `fetch` call
+    pub(crate) fn fetch_error(value: JsValue) -> Self {
+        match error_name(&value).as_deref() {
+            Some(TIMEOUT_ERROR) => Self::TimedOut(JsErrorValue::timed_out(value)),
+            Some(ABORT_ERROR) => Self::Aborted(JsErrorValue::aborted(value)),
+            _ if value.is_instance_of::<TypeError>() => {
+                Self::NetworkError(JsErrorValue::transport_failure(value))
+            }
+            _ => Self::js_error(value),
+        }
+    }
+
+    /// Initialize error from a failed read of response body stream
+    pub(crate) fn stream_error(value: JsValue) -> Self {
+        match error_name(&value).as_deref() {
+            Some(TIMEOUT_ERROR) => Self::TimedOut(JsErrorValue::timed_out(value)),
+            Some(ABORT_ERROR) => Self::Aborted(JsErrorValue::aborted(value)),
+            _ => Self::StreamReadError(JsErrorValue::transport_failure(value)),
+        }
+    }
+
+    /// Returns a copy of the error which is handed over to one of the callers sharing a call
+    ///
+    /// Errors which can not be copied are converted to [`Status`].
+    pub(crate) fn share(&self) -> Self {
+        match self {
+            Self::Aborted(value) => Self::Aborted(value.clone()),
+            Self::Base64DecodeError(error) => Self::Base64DecodeError(error.clone()),
+            Self::HeaderParsingError => Self::HeaderParsingError,
+            Self::InvalidBaseUrl(url) => Self::InvalidBaseUrl(url.clone()),
+            Self::InvalidContentType(content_type) => {
+                Self::InvalidContentType(content_type.clone())
+            }
+            Self::InvalidGrpcTimeout(timeout) => Self::InvalidGrpcTimeout(timeout.clone()),
+            Self::JsError(message) => Self::JsError(message.clone()),
+            Self::MalformedResponse => Self::MalformedResponse,
+            Self::MissingContentTypeHeader => Self::MissingContentTypeHeader,
+            Self::MissingResponseBody => Self::MissingResponseBody,
+            Self::NetworkError(value) => Self::NetworkError(value.clone()),
+            Self::RequestStreamingNotSupported => Self::RequestStreamingNotSupported,
+            Self::StreamReadError(value) => Self::StreamReadError(value.clone()),
+            Self::TimedOut(value) => Self::TimedOut(value.clone()),
+            Self::TonicStatusError(status) => Self::TonicStatusError(status.clone()),
+            error => Self::TonicStatusError(Status::unknown(error.to_string())),
+        }
+    }
+}
+
+impl From<Error> for Status {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::TonicStatusError(status) => status,
+            error => Status::from_error(Box::new(error)),
+        }
+    }
+}
+
+/// Returns `true` if `status` was caused by a network error, a failed read of a response body or a malformed response
+///
+/// Failures are recognized by the original error in the source chain of `status`, so statuses sent by servers are never
+/// mistaken for them.
+pub(crate) fn is_transport_failure(status: &Status) -> bool {
+    let mut source = std::error::Error::source(status);
+
+    while let Some(error) = source {
+        if let Some(Error::MalformedResponse | Error::NetworkError(_) | Error::StreamReadError(_)) =
+            error.downcast_ref::<Error>()
+        {
+            return true;
+        }
+        source = error.source();
+    }
+
+    false
+}
+
+/// Error value thrown by a JS API
+///
+/// The source of this error is the [`Status`] the error maps to, which allows `tonic` to report a meaningful status
+/// code for it.
+#[derive(Debug, Clone)]
+pub struct JsErrorValue {
+    value: JsValue,
+    message: String,
+    status: Status,
+}
+
+impl JsErrorValue {
+    fn new(value: JsValue, status: Status) -> Self {
+        Self {
+            message: js_object_display(&value),
+            value,
+            status,
+        }
+    }
+
+    fn transport_failure(value: JsValue) -> Self {
         let message = js_object_display(&value);
+        Self::new(value, Status::unavailable(message))
+    }
 
-        if message.contains("tonic_web_wasm_client::Error::TimedOut") {
-            Self::TonicStatusError(tonic::Status::deadline_exceeded("Request timed out"))
-        } else {
-            Self::JsError(message)
+    /// Creates an error value of a transport failure without a JS value, as JS APIs are not available in native tests
+    #[cfg(test)]
+    pub(crate) fn mock_transport_failure(message: &str) -> Self {
+        Self {
+            value: JsValue::UNDEFINED,
+            message: message.to_string(),
+            status: Status::unavailable(message),
         }
     }
+
+    fn timed_out(value: JsValue) -> Self {
+        Self::new(value, Status::deadline_exceeded("Request timed out"))
+    }
+
+    fn aborted(value: JsValue) -> Self {
+        Self::new(value, Status::cancelled("Request aborted"))
+    }
+
+    /// Returns the original JS value
+    pub fn value(&self) -> &JsValue {
+        &self.value
+    }
+
+    /// Returns the name of the JS error (e.g. `TypeError` or `AbortError`), if available
+    pub fn name(&self) -> Option<String> {
+        error_name(&self.value)
+    }
+}
+
+impl fmt::Display for JsErrorValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for JsErrorValue {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.status)
+    }
+}
+
+// `JsValue` can only be used on the thread that created it. This is only sound on wasm targets without the `atomics`
+// target feature, which always run on a single thread.
+#[cfg(all(target_arch = "wasm32", not(target_feature = "atomics")))]
+unsafe impl Send for JsErrorValue {}
+#[cfg(all(target_arch = "wasm32", not(target_feature = "atomics")))]
+unsafe impl Sync for JsErrorValue {}
+
+fn error_name(value: &JsValue) -> Option<String> {
+    if let Some(exception) = value.dyn_ref::<DomException>() {
+        Some(exception.name())
+    } else {
+        value
+            .dyn_ref::<js_sys::Error>()
+            .map(|error| error.name().into())
+    }
 }
 
 fn js_object_display(option: &JsValue) -> String {
@@ -1,32 +1,271 @@
-use js_sys::Promise;
+use std::{cell::OnceCell, future::Future, pin::Pin, rc::Rc, sync::Arc, time::Duration};
+
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use http::{HeaderMap, HeaderValue, Request, Response, StatusCode, header::HeaderName};
+use js_sys::{Array, Function, Promise, Uint8Array};
+use tonic::Status;
 use wasm_bindgen::{JsCast, JsValue, prelude::wasm_bindgen};
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{Request, RequestInit, Response};
+use web_sys::{Headers, ReadableStream, RequestCredentials, RequestInit};
+
+use crate::{Error, body_stream::BodyStream, options::FetchOptions};
+
+/// Body of a request passed to a [`FetchBackend`]
+pub enum FetchBody {
+    /// No request body (e.g. for `GET` requests)
+    Empty,
+    /// Fully buffered request body
+    Full(Bytes),
+    /// Request body which is streamed to the server as it is produced
+    Streaming(Pin<Box<dyn Stream<Item = Result<Bytes, Status>>>>),
+}
+
+/// Future returned by [`FetchBackend::fetch`] (response body is `None` if the response does not have a body)
+pub type FetchFuture = Pin<Box<dyn Future<Output = Result<Response<Option<BodyStream>>, Error>>>>;
+
+/// Executes HTTP requests on behalf of [`Client`](crate::Client)
+///
+/// [`JsFetch`] is used by default. Implement this trait to send requests using a different mechanism, e.g. to return
+/// canned responses in tests.
+pub trait FetchBackend {
+    /// Sends `request` and resolves once response headers are received. `timeout` limits the duration of the whole
+    /// call, including reading the response body.
+    fn fetch(
+        &self,
+        request: Request<FetchBody>,
+        options: &FetchOptions,
+        timeout: Option<Duration>,
+    ) -> FetchFuture;
+}
+
+impl<T: FetchBackend + ?Sized> FetchBackend for Rc<T> {
+    fn fetch(
+        &self,
+        request: Request<FetchBody>,
+        options: &FetchOptions,
+        timeout: Option<Duration>,
+    ) -> FetchFuture {
+        (**self).fetch(request, options, timeout)
+    }
+}
+
+impl<T: FetchBackend + ?Sized> FetchBackend for Arc<T> {
+    fn fetch(
+        &self,
+        request: Request<FetchBody>,
+        options: &FetchOptions,
+        timeout: Option<Duration>,
+    ) -> FetchFuture {
+        (**self).fetch(request, options, timeout)
+    }
+}
+
+/// [`FetchBackend`] implementation using JavaScript's `fetch` API
+///
+/// By default, `fetch` of the global scope is used. This works in browser windows, web workers (including service
+/// workers), Node.js and Deno.
+#[derive(Debug, Clone, Default)]
+pub struct JsFetch {
+    function: Option<Function>,
+}
 
-use crate::Error;
+impl JsFetch {
+    /// Creates a backend using `fetch` of the global scope
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a backend using a custom JavaScript function with the same signature as `fetch` (e.g. an instrumented
+    /// `fetch` provided by the application)
+    ///
+    /// The function is called with a `Request` and request init object and must return a promise resolving to a
+    /// `Response`.
+    pub fn with_function(function: Function) -> Self {
+        Self {
+            function: Some(function),
+        }
+    }
+}
+
+impl FetchBackend for JsFetch {
+    fn fetch(
+        &self,
+        request: Request<FetchBody>,
+        options: &FetchOptions,
+        timeout: Option<Duration>,
+    ) -> FetchFuture {
+        let prepared = prepare_request(request)
+            .and_then(|request| Ok((request, options.request_init(timeout)?)));
+        let function = self.function.clone();
+
+        Box::pin(async move {
+            let (request, (init, abort)) = prepared?;
+            let response = fetch(function.as_ref(), &request, &init).await?;
+
+            let status = StatusCode::from_u16(response.status()).map_err(|_| {
+                Status::unknown(format!("received HTTP status {}", response.status()))
+            })?;
+            let headers = response_headers(&response)?;
+
+            let body_stream = response.body().map(|body_stream| {
+                let body_stream =
+                    wasm_streams::ReadableStream::from_raw(body_stream.unchecked_into())
+                        .into_stream();
+                BodyStream::new(body_stream, abort)
+            });
+
+            let mut result = Response::new(body_stream);
+            *result.status_mut() = status;
+            *result.headers_mut() = headers;
+
+            Ok(result)
+        })
+    }
+}
 
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_name = fetch)]
-    fn fetch_with_request_and_init(input: &Request, init: &RequestInit) -> Promise;
+    fn fetch_with_request_and_init(input: &web_sys::Request, init: &RequestInit) -> Promise;
 }
 
-fn js_fetch(request: &Request, init: &RequestInit) -> Promise {
+fn js_fetch(
+    function: Option<&Function>,
+    request: &web_sys::Request,
+    init: &RequestInit,
+) -> Result<Promise, Error> {
+    if let Some(function) = function {
+        let promise = function
+            .call2(&JsValue::UNDEFINED, request, init)
+            .map_err(Error::fetch_error)?;
+        return Ok(Promise::resolve(&promise));
+    }
+
     let global = js_sys::global();
-    let key = JsValue::from_str("ServiceWorkerGlobalScope");
+    let key = JsValue::from_str("WorkerGlobalScope");
 
     match js_sys::Reflect::has(&global, &key) {
-        Ok(true) => global
-            .unchecked_into::<web_sys::ServiceWorkerGlobalScope>()
-            .fetch_with_request_and_init(request, init),
-        _ => fetch_with_request_and_init(request, init),
+        Ok(true) => Ok(global
+            .unchecked_into::<web_sys::WorkerGlobalScope>()
+            .fetch_with_request_and_init(request, init)),
+        _ => Ok(fetch_with_request_and_init(request, init)),
     }
 }
 
-pub async fn fetch(request: &Request, init: &RequestInit) -> Result<Response, Error> {
-    let js_response = JsFuture::from(js_fetch(request, init))
+async fn fetch(
+    function: Option<&Function>,
+    request: &web_sys::Request,
+    init: &RequestInit,
+) -> Result<web_sys::Response, Error> {
+    let js_response = JsFuture::from(js_fetch(function, request, init)?)
         .await
-        .map_err(Error::js_error)?;
+        .map_err(Error::fetch_error)?;
 
     Ok(js_response.unchecked_into())
 }
+
+fn prepare_request(request: Request<FetchBody>) -> Result<web_sys::Request, Error> {
+    let (parts, body) = request.into_parts();
+
+    let headers = Headers::new().map_err(Error::js_error)?;
+    for (header_name, header_value) in parts.headers.iter() {
+        headers
+            .append(header_name.as_str(), header_value.to_str()?)
+            .map_err(Error::js_error)?;
+    }
+
+    let init = RequestInit::new();
+
+    init.set_method(parts.method.as_str());
+    init.set_headers(headers.as_ref());
+    match body {
+        FetchBody::Empty => {}
+        FetchBody::Full(bytes) => init.set_body(&Uint8Array::from(bytes.as_ref())),
+        FetchBody::Streaming(body_stream) => {
+            if !supports_request_streams() {
+                return Err(Error::RequestStreamingNotSupported);
+            }
+
+            let body_stream = body_stream.map(|result| match result {
+                Ok(bytes) => Ok(Uint8Array::from(bytes.as_ref()).into()),
+                Err(status) => Err(JsValue::from_str(&status.to_string())),
+            });
+            let body_stream = wasm_streams::ReadableStream::from_stream(body_stream).into_raw();
+
+            init.set_body(&body_stream);
+            set_half_duplex(&init);
+        }
+    }
+    init.set_credentials(RequestCredentials::SameOrigin);
+
+    web_sys::Request::new_with_str_and_init(&parts.uri.to_string(), &init).map_err(Error::js_error)
+}
+
+pub(crate) fn response_headers(response: &web_sys::Response) -> Result<HeaderMap, Error> {
+    let headers = response.headers();
+
+    let header_iter = js_sys::try_iter(headers.as_ref()).map_err(Error::js_error)?;
+
+    let mut result = HeaderMap::new();
+
+    if let Some(header_iter) = header_iter {
+        for header in header_iter {
+            let header = header.map_err(Error::js_error)?;
+            let pair: Array = header.into();
+
+            let header_name = pair.get(0).as_string();
+            let header_value = pair.get(1).as_string();
+
+            match (header_name, header_value) {
+                (Some(header_name), Some(header_value)) => {
+                    result.append(
+                        HeaderName::from_bytes(header_name.as_bytes())?,
+                        HeaderValue::from_str(&header_value)?,
+                    );
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+thread_local! {
+    static SUPPORTS_REQUEST_STREAMS: OnceCell<bool> = const { OnceCell::new() };
+}
+
+/// Returns `true` if the browser supports `ReadableStream` request bodies.
+///
+/// Browsers without support for streaming uploads stringify the stream and add a `text/plain` content type instead of
+/// sending the stream as request body.
+fn supports_request_streams() -> bool {
+    SUPPORTS_REQUEST_STREAMS.with(|supported| *supported.get_or_init(detect_request_streams))
+}
+
+fn detect_request_streams() -> bool {
+    let Ok(stream) = ReadableStream::new() else {
+        return false;
+    };
+
+    let init = RequestInit::new();
+    init.set_method("POST");
+    init.set_body(&stream);
+    set_half_duplex(&init);
+
+    match web_sys::Request::new_with_str_and_init("data:,", &init) {
+        Ok(request) => !request.headers().has("content-type").unwrap_or(true),
+        Err(_) => false,
+    }
+}
+
+/// Sets `duplex: "half"` on request init which is required for sending `ReadableStream` request bodies.
+fn set_half_duplex(init: &RequestInit) {
+    // `web-sys` does not expose a setter for `duplex`. Setting it can only fail if `init` is not an object.
+    let _ = js_sys::Reflect::set(
+        init,
+        &JsValue::from_str("duplex"),
+        &JsValue::from_str("half"),
+    );
+}
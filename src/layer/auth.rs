@@ -0,0 +1,360 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    future::{Future, poll_fn},
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use http::{HeaderName, HeaderValue, Request, Response, header::AUTHORIZATION};
+use tonic::{Code, body::Body};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{Error, ResponseBody, layer::BufferedRequest};
+
+const GRPC_STATUS: &str = "grpc-status";
+
+type TokenFuture = Pin<Box<dyn Future<Output = Result<HeaderValue, Error>>>>;
+
+type TokenFn = dyn Fn(bool) -> TokenFuture;
+
+/// Layer which sets an authorization header fetched from an async callback before each call
+///
+/// The callback is called with `refresh` set to `false` before sending a request. If the server responds with
+/// [`Code::Unauthenticated`], the callback is called again with `refresh` set to `true`.
+///
+/// Calls of methods registered with [`AuthLayer::unary_method`] are replayed once with the new header value. Their
+/// request bodies are buffered to allow replaying them, and their responses are read until the first message or the
+/// final status is available. Calls of other methods (which may stream requests or responses) are neither buffered nor
+/// replayed: they are classified from response headers or a trailers-only response, and fail with the original status
+/// after the token is refreshed. Use [`AuthLayer::replay_unauthenticated`] to disable refreshing and replaying.
+#[derive(Clone)]
+pub struct AuthLayer {
+    token: Rc<TokenFn>,
+    header_name: HeaderName,
+    replay_unauthenticated: bool,
+    unary_methods: Rc<HashSet<String>>,
+}
+
+impl AuthLayer {
+    /// Creates a new auth layer which sets the `authorization` header to the value returned by `token`
+    pub fn new<F, Fut>(token: F) -> Self
+    where
+        F: Fn(bool) -> Fut + 'static,
+        Fut: Future<Output = Result<HeaderValue, Error>> + 'static,
+    {
+        Self {
+            token: Rc::new(move |refresh| Box::pin(token(refresh))),
+            header_name: AUTHORIZATION,
+            replay_unauthenticated: true,
+            unary_methods: Rc::new(HashSet::new()),
+        }
+    }
+
+    /// Sets the name of the header containing the token (defaults to `authorization`)
+    pub fn header_name(mut self, header_name: HeaderName) -> Self {
+        self.header_name = header_name;
+        self
+    }
+
+    /// Sets whether requests rejected with [`Code::Unauthenticated`] are replayed with a refreshed token (defaults to
+    /// `true`)
+    pub fn replay_unauthenticated(mut self, replay_unauthenticated: bool) -> Self {
+        self.replay_unauthenticated = replay_unauthenticated;
+        self
+    }
+
+    /// Registers a unary method (e.g. `/echo.Echo/Echo`) whose calls are replayed after refreshing the token
+    pub fn unary_method(mut self, path: impl Into<String>) -> Self {
+        Rc::make_mut(&mut self.unary_methods).insert(path.into());
+        self
+    }
+}
+
+impl fmt::Debug for AuthLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthLayer")
+            .field("header_name", &self.header_name)
+            .field("replay_unauthenticated", &self.replay_unauthenticated)
+            .field("unary_methods", &self.unary_methods)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = AuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// Service created by [`AuthLayer`]
+#[derive(Debug, Clone)]
+pub struct AuthService<S> {
+    inner: S,
+    layer: AuthLayer,
+}
+
+impl<S> Service<Request<Body>> for AuthService<S>
+where
+    S: Service<Request<Body>, Response = Response<ResponseBody>, Error = Error> + Clone + 'static,
+{
+    type Response = Response<ResponseBody>;
+
+    type Error = Error;
+
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // Use the service which was driven to readiness and leave a clone in its place
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+
+        Box::pin(async move {
+            let token = (layer.token)(false).await?;
+
+            if !layer.replay_unauthenticated {
+                let mut request = request;
+                request.headers_mut().insert(layer.header_name, token);
+                return inner.call(request).await;
+            }
+
+            if !layer.unary_methods.contains(request.uri().path()) {
+                let mut request = request;
+                request.headers_mut().insert(layer.header_name, token);
+                let result = inner.call(request).await;
+
+                // Waiting for the first message of a stream would delay initial metadata, or never resolve for idle
+                // streams
+                if is_unauthenticated(&result) {
+                    (layer.token)(true).await?;
+                }
+                return result;
+            }
+
+            let mut request = BufferedRequest::new(request).await?;
+            request
+                .headers_mut()
+                .insert(layer.header_name.clone(), token);
+
+            let mut result = inner.call(request.to_request()).await;
+
+            if !prefetch_unauthenticated(&mut result).await {
+                return result;
+            }
+
+            // The rejected response may hold a slot of the concurrency limit which is needed by the replay
+            drop(result);
+
+            let token = (layer.token)(true).await?;
+            request.headers_mut().insert(layer.header_name, token);
+
+            poll_fn(|cx| inner.poll_ready(cx)).await?;
+            inner.call(request.to_request()).await
+        })
+    }
+}
+
+/// Returns `true` if the call failed with [`Code::Unauthenticated`] before receiving any message, reading the response
+/// until the first message or the final status is available
+async fn prefetch_unauthenticated(result: &mut Result<Response<ResponseBody>, Error>) -> bool {
+    if let Ok(response) = result {
+        let body = response.body_mut();
+
+        if let Err(error) = poll_fn(|cx| Pin::new(&mut *body).poll_prefetch(cx)).await {
+            *result = Err(error);
+        }
+    }
+
+    is_unauthenticated(result)
+}
+
+/// Returns `true` if the call failed with [`Code::Unauthenticated`] in response headers or a trailers-only response
+fn is_unauthenticated(result: &Result<Response<ResponseBody>, Error>) -> bool {
+    match result {
+        Ok(response) => response
+            .body()
+            .trailers_only()
+            .and_then(|trailers| trailers.get(GRPC_STATUS))
+            .and_then(|status| status.to_str().ok())
+            .and_then(|status| status.parse::<i32>().ok())
+            .is_some_and(|status| Code::from_i32(status) == Code::Unauthenticated),
+        Err(Error::TonicStatusError(status)) => status.code() == Code::Unauthenticated,
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use bytes::Bytes;
+    use futures_util::FutureExt;
+    use http_body_util::Full;
+
+    use super::*;
+    use crate::{ClientBuilder, mock_fetch::MockFetch, options::ConcurrencyLimit};
+
+    fn auth_layer(calls: Rc<Cell<u32>>) -> AuthLayer {
+        AuthLayer::new(move |refresh| {
+            let calls = calls.clone();
+            async move {
+                calls.set(calls.get() + 1);
+                Ok(HeaderValue::from_static(if refresh {
+                    "Bearer refreshed"
+                } else {
+                    "Bearer cached"
+                }))
+            }
+        })
+    }
+
+    fn request() -> Request<Body> {
+        let mut request = Request::new(Body::new(Full::new(Bytes::from_static(b"hello"))));
+        *request.uri_mut() = "/echo.Echo/Echo".parse().unwrap();
+        request
+    }
+
+    #[test]
+    fn test_auth_layer() {
+        let vals = [
+            (MockFetch::grpc_web(), true, vec!["Bearer cached"]),
+            (
+                MockFetch::grpc_web().header("grpc-status", "16"),
+                true,
+                vec!["Bearer cached", "Bearer refreshed"],
+            ),
+            (
+                MockFetch::new().status(401),
+                true,
+                vec!["Bearer cached", "Bearer refreshed"],
+            ),
+            (
+                MockFetch::grpc_web().header("grpc-status", "16"),
+                false,
+                vec!["Bearer cached"],
+            ),
+            (
+                MockFetch::grpc_web().header("grpc-status", "14"),
+                true,
+                vec!["Bearer cached"],
+            ),
+        ];
+
+        for (fetch, replay, expected) in vals {
+            let fetch = Rc::new(fetch);
+            let calls = Rc::new(Cell::new(0));
+
+            let mut client = ClientBuilder::new("http://localhost:50051".to_string())
+                .backend(fetch.clone())
+                .layer(
+                    auth_layer(calls.clone())
+                        .unary_method("/echo.Echo/Echo")
+                        .replay_unauthenticated(replay),
+                )
+                .build()
+                .unwrap();
+
+            poll_fn(|cx| client.poll_ready(cx))
+                .now_or_never()
+                .unwrap()
+                .unwrap();
+            let _ = client.call(request()).now_or_never().expect("ready call");
+
+            let requests = fetch.requests();
+            let authorization: Vec<_> = requests
+                .iter()
+                .map(|request| request.request.headers()[AUTHORIZATION].to_str().unwrap())
+                .collect();
+
+            assert_eq!(authorization, expected);
+            assert_eq!(calls.get() as usize, expected.len());
+            assert!(
+                requests
+                    .iter()
+                    .all(|request| request.request.body().as_deref() == Some(&b"hello"[..]))
+            );
+        }
+    }
+
+    #[test]
+    fn test_auth_layer_unregistered() {
+        let vals = [
+            (MockFetch::grpc_web().idle(), Some(true), 1),
+            (
+                MockFetch::grpc_web().header("grpc-status", "16"),
+                Some(true),
+                2,
+            ),
+            (MockFetch::new().status(401), Some(false), 2),
+        ];
+
+        for (fetch, ok, expected_calls) in vals {
+            let fetch = Rc::new(fetch);
+            let calls = Rc::new(Cell::new(0));
+
+            let mut client = ClientBuilder::new("http://localhost:50051".to_string())
+                .backend(fetch.clone())
+                .layer(auth_layer(calls.clone()))
+                .build()
+                .unwrap();
+
+            poll_fn(|cx| client.poll_ready(cx))
+                .now_or_never()
+                .unwrap()
+                .unwrap();
+
+            // Resolves on response headers without waiting for a message of the idle stream
+            let response = client.call(request()).now_or_never();
+            assert_eq!(response.map(|response| response.is_ok()), ok);
+
+            // The token is refreshed, but the request is not replayed
+            assert_eq!(fetch.requests().len(), 1);
+            assert_eq!(calls.get(), expected_calls);
+        }
+    }
+
+    #[test]
+    fn test_auth_layer_concurrency_limit() {
+        let fetch = Rc::new(MockFetch::grpc_web().header("grpc-status", "16"));
+        let calls = Rc::new(Cell::new(0));
+
+        let mut client = ClientBuilder::new("http://localhost:50051".to_string())
+            .backend(fetch.clone())
+            .concurrency_limit(ConcurrencyLimit::new().max_unary(1))
+            .layer(
+                auth_layer(calls.clone())
+                    .unary_method("/echo.Echo/Echo")
+                    .replay_unauthenticated(true),
+            )
+            .build()
+            .unwrap();
+
+        poll_fn(|cx| client.poll_ready(cx))
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+
+        // Replay does not wait for the slot held by the rejected response
+        let response = client.call(request()).now_or_never().expect("ready call");
+        assert!(response.is_ok());
+        assert_eq!(fetch.requests().len(), 2);
+
+        // Only the replayed response holds a slot
+        let mut cx = Context::from_waker(std::task::Waker::noop());
+        assert!(client.poll_ready(&mut cx).is_pending());
+        drop(response);
+        assert!(client.poll_ready(&mut cx).is_ready());
+    }
+}
@@ -0,0 +1,103 @@
+use bytes::Bytes;
+use http::{Extensions, HeaderMap, Method, Request, Uri, Version};
+use http_body_util::{BodyExt, Full};
+use tonic::body::Body;
+
+use crate::Error;
+
+/// Request with a fully buffered body which can be sent multiple times
+///
+/// Middleware which needs to replay requests (e.g. after refreshing credentials) can buffer the request once and
+/// create a new [`Request`] for each attempt using [`BufferedRequest::to_request`].
+#[derive(Debug, Clone)]
+pub struct BufferedRequest {
+    method: Method,
+    uri: Uri,
+    version: Version,
+    headers: HeaderMap,
+    extensions: Extensions,
+    body: Bytes,
+}
+
+impl BufferedRequest {
+    /// Buffers the body of `request`
+    pub async fn new(request: Request<Body>) -> Result<Self, Error> {
+        let (parts, body) = request.into_parts();
+        let body = body.collect().await?.to_bytes();
+
+        Ok(Self {
+            method: parts.method,
+            uri: parts.uri,
+            version: parts.version,
+            headers: parts.headers,
+            extensions: parts.extensions,
+            body,
+        })
+    }
+
+    /// Returns the headers of the request
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Returns a mutable reference to the headers of the request
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+
+    /// Returns the buffered body of the request
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// Creates a new request with a copy of the buffered request
+    pub fn to_request(&self) -> Request<Body> {
+        let mut request = Request::new(Body::new(Full::new(self.body.clone())));
+
+        *request.method_mut() = self.method.clone();
+        *request.uri_mut() = self.uri.clone();
+        *request.version_mut() = self.version;
+        *request.headers_mut() = self.headers.clone();
+        *request.extensions_mut() = self.extensions.clone();
+
+        request
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::FutureExt;
+
+    use super::*;
+
+    #[test]
+    fn test_to_request() {
+        let mut request = Request::new(Body::new(Full::new(Bytes::from_static(b"hello"))));
+        *request.uri_mut() = "/echo.Echo/Echo".parse().unwrap();
+        request
+            .headers_mut()
+            .insert("x-session-id", "session-1".parse().unwrap());
+        request.extensions_mut().insert(42u32);
+
+        let buffered = BufferedRequest::new(request)
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+
+        for _ in 0..2 {
+            let request = buffered.to_request();
+
+            assert_eq!(request.uri(), "/echo.Echo/Echo");
+            assert_eq!(request.headers()["x-session-id"], "session-1");
+            assert_eq!(request.extensions().get::<u32>(), Some(&42));
+
+            let body = request
+                .into_body()
+                .collect()
+                .now_or_never()
+                .unwrap()
+                .unwrap();
+            assert_eq!(body.to_bytes(), &b"hello"[..]);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use super::{CacheBackend, CacheEntry, CacheFuture};
+use crate::timer::now;
+
+/// In-memory [`CacheBackend`] which evicts expired entries, and then the least recently used entry, when full
+#[derive(Debug)]
+pub struct MemoryCache {
+    capacity: usize,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    entries: HashMap<String, (CacheEntry, u64)>,
+    clock: u64,
+}
+
+impl State {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+}
+
+impl MemoryCache {
+    /// Creates a new cache holding at most `capacity` entries
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Default::default(),
+        }
+    }
+
+    fn with_state<T>(&self, f: impl FnOnce(&mut State) -> T) -> T {
+        f(&mut self.state.lock().unwrap_or_else(|error| error.into_inner()))
+    }
+}
+
+impl CacheBackend for MemoryCache {
+    fn get(&self, key: &str) -> CacheFuture<Option<CacheEntry>> {
+        let entry = self.with_state(|state| {
+            let tick = state.tick();
+
+            state.entries.get_mut(key).map(|(entry, last_used)| {
+                *last_used = tick;
+                entry.clone()
+            })
+        });
+
+        Box::pin(async move { Ok(entry) })
+    }
+
+    fn put(&self, key: &str, entry: CacheEntry) -> CacheFuture<()> {
+        self.with_state(|state| {
+            let tick = state.tick();
+            state.entries.insert(key.to_owned(), (entry, tick));
+
+            if state.entries.len() > self.capacity {
+                let now = now();
+                state.entries.retain(|_, (entry, _)| entry.expires_at > now);
+            }
+
+            while state.entries.len() > self.capacity {
+                let least_recently_used = state
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, (_, last_used))| *last_used)
+                    .map(|(key, _)| key.clone());
+
+                match least_recently_used {
+                    Some(key) => state.entries.remove(&key),
+                    None => break,
+                };
+            }
+        });
+
+        Box::pin(async { Ok(()) })
+    }
+
+    fn remove_prefix(&self, prefix: &str) -> CacheFuture<()> {
+        self.with_state(|state| state.entries.retain(|key, _| !key.starts_with(prefix)));
+
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use futures_util::FutureExt;
+    use http::HeaderMap;
+
+    use super::*;
+
+    fn entry() -> CacheEntry {
+        CacheEntry {
+            headers: HeaderMap::new(),
+            body: Bytes::new(),
+            trailers: HeaderMap::new(),
+            expires_at: f64::INFINITY,
+        }
+    }
+
+    #[test]
+    fn test_memory_cache() {
+        let vals = [
+            // Reading `/a/1` makes `/a/2` the least recently used entry
+            (None, vec!["/a/1", "/b/1"]),
+            (Some("/a/"), vec!["/b/1"]),
+            (Some("/"), vec![]),
+        ];
+
+        for (prefix, expected) in vals.iter() {
+            let cache = MemoryCache::new(2);
+            cache.put("/a/1", entry()).now_or_never().unwrap().unwrap();
+            cache.put("/a/2", entry()).now_or_never().unwrap().unwrap();
+            cache.get("/a/1").now_or_never().unwrap().unwrap();
+            cache.put("/b/1", entry()).now_or_never().unwrap().unwrap();
+
+            if let Some(prefix) = prefix {
+                cache.remove_prefix(prefix).now_or_never().unwrap().unwrap();
+            }
+
+            for key in ["/a/1", "/a/2", "/b/1"] {
+                let found = cache.get(key).now_or_never().unwrap().unwrap().is_some();
+                assert_eq!(found, expected.contains(&key), "{key}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_memory_cache_expired() {
+        let cache = MemoryCache::new(2);
+        let expired = CacheEntry {
+            expires_at: 0.0,
+            ..entry()
+        };
+
+        cache.put("/a/1", entry()).now_or_never().unwrap().unwrap();
+        cache.put("/a/2", expired).now_or_never().unwrap().unwrap();
+        cache.put("/b/1", entry()).now_or_never().unwrap().unwrap();
+
+        // Expired entry is evicted instead of the least recently used one
+        for (key, expected) in [("/a/1", true), ("/a/2", false), ("/b/1", true)] {
+            let found = cache.get(key).now_or_never().unwrap().unwrap().is_some();
+            assert_eq!(found, expected, "{key}");
+        }
+    }
+}
@@ -0,0 +1,410 @@
+mod memory;
+mod storage;
+
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use bytes::Bytes;
+use http::{HeaderMap, HeaderName, Request, Response, header::AUTHORIZATION};
+use http_body_util::BodyExt;
+use sha2::{Digest, Sha256};
+use tonic::body::Body;
+use tower_layer::Layer;
+use tower_service::Service;
+
+pub use self::{memory::MemoryCache, storage::BrowserCache};
+use crate::{Error, ResponseBody, layer::BufferedRequest, timer::now};
+
+const GRPC_STATUS: &str = "grpc-status";
+
+/// Future returned by [`CacheBackend`] methods
+pub type CacheFuture<T> = Pin<Box<dyn Future<Output = Result<T, Error>>>>;
+
+/// Storage of cached responses used by [`CacheLayer`]
+///
+/// Keys have the form `{path}/{message}/{metadata}` (e.g. `/echo.Echo/Echo/{43 characters}/{43 characters}`), where
+/// `message` is the SHA-256 digest of the encoded request body and `metadata` is the SHA-256 digest of the values of
+/// the metadata the response varies on (see [`CacheLayer::vary`]), both in URL safe base64 without padding. Backends
+/// return entries regardless of their expiry time, but may evict expired entries at any time.
+pub trait CacheBackend {
+    /// Returns the entry stored with `key`, if any
+    fn get(&self, key: &str) -> CacheFuture<Option<CacheEntry>>;
+
+    /// Stores `entry` with `key`, replacing any existing entry
+    fn put(&self, key: &str, entry: CacheEntry) -> CacheFuture<()>;
+
+    /// Removes all entries whose key starts with `prefix`
+    fn remove_prefix(&self, prefix: &str) -> CacheFuture<()>;
+}
+
+/// Cached response of a successful unary call
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    /// Response headers
+    pub headers: HeaderMap,
+    /// Response body containing encoded message frames
+    pub body: Bytes,
+    /// Response trailers
+    pub trailers: HeaderMap,
+    /// Time when the entry expires in milliseconds since the Unix epoch
+    pub expires_at: f64,
+}
+
+impl CacheEntry {
+    /// Buffers `response` if it is successful
+    async fn from_response(
+        response: Response<ResponseBody>,
+        expires_at: f64,
+    ) -> Result<Result<Self, Response<ResponseBody>>, Error> {
+        let (parts, body) = response.into_parts();
+        let collected = body.collect().await?;
+        let trailers = collected.trailers().cloned().unwrap_or_default();
+
+        let entry = Self {
+            headers: parts.headers,
+            body: collected.to_bytes(),
+            trailers,
+            expires_at,
+        };
+
+        if entry.is_success() {
+            Ok(Ok(entry))
+        } else {
+            let mut response = entry.to_response();
+            *response.status_mut() = parts.status;
+            *response.version_mut() = parts.version;
+            *response.extensions_mut() = parts.extensions;
+
+            Ok(Err(response))
+        }
+    }
+
+    fn is_success(&self) -> bool {
+        self.trailers
+            .get(GRPC_STATUS)
+            .is_some_and(|status| status == "0")
+    }
+
+    fn to_response(&self) -> Response<ResponseBody> {
+        let body = if self.body.is_empty() {
+            ResponseBody::from_trailers(self.trailers.clone())
+        } else {
+            ResponseBody::from_message(self.body.as_ref().into(), self.trailers.clone())
+        };
+
+        let mut response = Response::new(body);
+        *response.headers_mut() = self.headers.clone();
+        response
+    }
+}
+
+/// Layer which caches responses of registered idempotent unary methods
+///
+/// Responses are cached by path, request body and the values of metadata listed with [`CacheLayer::vary`] (only
+/// `authorization` by default, so that users of the same client never see each other's responses). Only methods whose
+/// response does not depend on other metadata should be registered. The layer must be added after layers setting vary
+/// metadata (e.g. [`AuthLayer`](crate::layer::AuthLayer)), as it only sees metadata set by outer layers. Successful
+/// responses are stored in a [`CacheBackend`] (e.g. [`MemoryCache`] or [`BrowserCache`]) for the TTL of the method and
+/// calls are answered from the cache until the entry expires or is invalidated. Failures of the backend are treated as
+/// cache misses.
+///
+/// Clones of the layer share the backend, so a clone kept by the application can be used to invalidate entries.
+///
+/// ```rust,ignore
+/// use std::time::Duration;
+///
+/// use tonic_web_wasm_client::{ClientBuilder, layer::{CacheLayer, MemoryCache}};
+///
+/// let cache = CacheLayer::new(MemoryCache::new(100))
+///     .method("/config.Config/GetConfig", Duration::from_secs(60));
+/// let client = ClientBuilder::new(base_url).layer(cache.clone()).build()?;
+///
+/// // After updating the configuration
+/// cache.invalidate_method("/config.Config/GetConfig").await?;
+/// ```
+#[derive(Clone)]
+pub struct CacheLayer {
+    backend: Rc<dyn CacheBackend>,
+    methods: HashMap<String, Duration>,
+    vary: Vec<HeaderName>,
+}
+
+impl CacheLayer {
+    /// Creates a new cache layer storing responses in `backend`
+    pub fn new(backend: impl CacheBackend + 'static) -> Self {
+        Self {
+            backend: Rc::new(backend),
+            methods: HashMap::new(),
+            vary: vec![AUTHORIZATION],
+        }
+    }
+
+    /// Registers a unary method (e.g. `/echo.Echo/Echo`) whose responses are cached for `ttl`
+    pub fn method(mut self, path: impl Into<String>, ttl: Duration) -> Self {
+        self.methods.insert(path.into(), ttl);
+        self
+    }
+
+    /// Adds metadata (e.g. a custom auth header) whose values are a part of the cache key, in addition to
+    /// `authorization`
+    pub fn vary(mut self, header_name: HeaderName) -> Self {
+        if !self.vary.contains(&header_name) {
+            self.vary.push(header_name);
+        }
+        self
+    }
+
+    /// Removes all cached responses of the method with `path` (e.g. `/echo.Echo/Echo`)
+    pub async fn invalidate_method(&self, path: &str) -> Result<(), Error> {
+        self.backend.remove_prefix(&format!("{path}/")).await
+    }
+
+    /// Removes all cached responses whose key starts with `prefix` (e.g. `/echo.Echo/` for all methods of a service)
+    pub async fn invalidate_prefix(&self, prefix: &str) -> Result<(), Error> {
+        self.backend.remove_prefix(prefix).await
+    }
+}
+
+impl fmt::Debug for CacheLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CacheLayer")
+            .field("methods", &self.methods)
+            .field("vary", &self.vary)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> Layer<S> for CacheLayer {
+    type Service = CacheService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CacheService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// Service created by [`CacheLayer`]
+#[derive(Debug, Clone)]
+pub struct CacheService<S> {
+    inner: S,
+    layer: CacheLayer,
+}
+
+impl<S> Service<Request<Body>> for CacheService<S>
+where
+    S: Service<Request<Body>, Response = Response<ResponseBody>, Error = Error> + Clone + 'static,
+{
+    type Response = Response<ResponseBody>;
+
+    type Error = Error;
+
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // Use the service which was driven to readiness and leave a clone in its place
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        let Some(ttl) = self.layer.methods.get(request.uri().path()).copied() else {
+            return Box::pin(async move { inner.call(request).await });
+        };
+
+        let backend = self.layer.backend.clone();
+        let vary = self.layer.vary.clone();
+
+        Box::pin(async move {
+            let path = request.uri().path().to_owned();
+            let request = BufferedRequest::new(request).await?;
+            let key = cache_key(&path, &request, &vary);
+
+            if let Ok(Some(entry)) = backend.get(&key).await
+                && entry.expires_at > now()
+            {
+                return Ok(entry.to_response());
+            }
+
+            let response = inner.call(request.to_request()).await?;
+            let expires_at = now() + ttl.as_secs_f64() * 1000.0;
+
+            match CacheEntry::from_response(response, expires_at).await? {
+                Ok(entry) => {
+                    let response = entry.to_response();
+                    let _ = backend.put(&key, entry).await;
+                    Ok(response)
+                }
+                Err(response) => Ok(response),
+            }
+        })
+    }
+}
+
+/// Returns the key of a request
+///
+/// The body and metadata values are hashed, so that keys have a bounded length and credentials are not stored in keys.
+fn cache_key(path: &str, request: &BufferedRequest, vary: &[HeaderName]) -> String {
+    let mut metadata = Sha256::new();
+    for header_name in vary {
+        let values = request.headers().get_all(header_name);
+
+        update_len_prefixed(&mut metadata, header_name.as_str().as_bytes());
+        metadata.update((values.iter().count() as u64).to_be_bytes());
+        for value in values {
+            update_len_prefixed(&mut metadata, value.as_bytes());
+        }
+    }
+
+    format!(
+        "{path}/{}/{}",
+        BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(request.body())),
+        BASE64_URL_SAFE_NO_PAD.encode(metadata.finalize())
+    )
+}
+
+/// Feeds `bytes` prefixed with their length into `hasher`, so that adjacent values can not be confused
+fn update_len_prefixed(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update((bytes.len() as u64).to_be_bytes());
+    hasher.update(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use futures_util::FutureExt;
+    use http::HeaderValue;
+    use http_body_util::Full;
+
+    use super::*;
+    use crate::{
+        ClientBuilder,
+        mock_fetch::{MockFetch, data_frame, trailer_frame},
+    };
+
+    fn request(path: &'static str) -> Request<Body> {
+        let mut request = Request::new(Body::new(Full::new(Bytes::from_static(b"hello"))));
+        *request.uri_mut() = path.parse().unwrap();
+        request
+    }
+
+    #[test]
+    fn test_cache_layer() {
+        let vals = [
+            ("/echo.Echo/Echo", "grpc-status: 0\r\n", 1),
+            ("/echo.Echo/Echo", "grpc-status: 13\r\n", 2),
+            ("/echo.Echo/EchoStream", "grpc-status: 0\r\n", 2),
+        ];
+
+        for (path, trailers, fetches) in vals.iter() {
+            let fetch = Rc::new(
+                MockFetch::grpc_web()
+                    .chunk(data_frame(b"echo"))
+                    .chunk(trailer_frame(trailers)),
+            );
+
+            let cache = CacheLayer::new(MemoryCache::new(10))
+                .method("/echo.Echo/Echo", Duration::from_secs(60));
+            let mut client = ClientBuilder::new("http://localhost:50051".to_string())
+                .backend(fetch.clone())
+                .layer(cache.clone())
+                .build()
+                .unwrap();
+
+            for _ in 0..2 {
+                let _ = client.call(request(path)).now_or_never().unwrap();
+            }
+            assert_eq!(fetch.requests().len(), *fetches);
+
+            if *fetches == 1 {
+                let collected = client
+                    .call(request(path))
+                    .now_or_never()
+                    .unwrap()
+                    .unwrap()
+                    .into_body()
+                    .collect()
+                    .now_or_never()
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(collected.trailers().unwrap()[GRPC_STATUS], "0");
+                assert_eq!(collected.to_bytes(), data_frame(b"echo"));
+
+                // Invalidated responses are fetched again
+                cache
+                    .invalidate_method(path)
+                    .now_or_never()
+                    .unwrap()
+                    .unwrap();
+                let _ = client.call(request(path)).now_or_never().unwrap();
+                assert_eq!(fetch.requests().len(), 2);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let mut request = request("/echo.Echo/Echo");
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, HeaderValue::from_static("Bearer a"));
+        let request = BufferedRequest::new(request)
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+
+        // Keys are stable between releases, as they are persisted by `BrowserCache`
+        assert_eq!(
+            cache_key("/echo.Echo/Echo", &request, &[AUTHORIZATION]),
+            "/echo.Echo/Echo/LPJNul-wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ/mniKWVYHEUaZqQs1S2kpgBM47DGO5hy3MxUT_VJ62iY"
+        );
+    }
+
+    #[test]
+    fn test_cache_vary() {
+        let vals = [
+            (AUTHORIZATION, "Bearer a", "Bearer a", 1),
+            (AUTHORIZATION, "Bearer a", "Bearer b", 2),
+            (HeaderName::from_static("x-api-key"), "a", "b", 2),
+        ];
+
+        for (header_name, first, second, fetches) in vals {
+            let fetch = Rc::new(
+                MockFetch::grpc_web()
+                    .chunk(data_frame(b"echo"))
+                    .chunk(trailer_frame("grpc-status: 0\r\n")),
+            );
+
+            let cache = CacheLayer::new(MemoryCache::new(10))
+                .method("/echo.Echo/Echo", Duration::from_secs(60))
+                .vary(HeaderName::from_static("x-api-key"));
+            let mut client = ClientBuilder::new("http://localhost:50051".to_string())
+                .backend(fetch.clone())
+                .layer(cache)
+                .build()
+                .unwrap();
+
+            for value in [first, second] {
+                let mut request = request("/echo.Echo/Echo");
+                request
+                    .headers_mut()
+                    .insert(header_name.clone(), HeaderValue::from_static(value));
+                let _ = client.call(request).now_or_never().unwrap();
+            }
+
+            assert_eq!(fetch.requests().len(), fetches);
+        }
+    }
+}
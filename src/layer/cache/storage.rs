@@ -0,0 +1,214 @@
+use bytes::Bytes;
+use http::{HeaderMap, HeaderName};
+use js_sys::{Array, Reflect, Uint8Array};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Cache, CacheStorage, Headers, ResponseInit};
+
+use super::{CacheBackend, CacheEntry, CacheFuture};
+use crate::{Error, fetch::response_headers, timer::now};
+
+/// Origin of URLs used as keys in Cache Storage (which only accepts `http` and `https` URLs)
+const CACHE_URL: &str = "https://tonic-web-wasm-client.invalid";
+
+/// Header storing the expiry time of an entry
+const EXPIRES_AT: &str = "x-cache-expires-at";
+
+/// Prefix of headers storing trailers of an entry
+const TRAILER_PREFIX: &str = "x-cache-trailer-";
+
+/// [`CacheBackend`] storing responses in the browser's Cache Storage, which persists across page loads
+///
+/// Expired entries are deleted when they are looked up and whenever a new entry is stored. Use [`BrowserCache::clear`]
+/// to delete all entries (e.g. on logout, as responses may be specific to the user). Cache Storage is only available in
+/// secure contexts (e.g. pages served over `https` or from `localhost`).
+#[derive(Debug, Clone)]
+pub struct BrowserCache {
+    name: String,
+}
+
+impl BrowserCache {
+    /// Creates a new backend storing responses in the cache named `name`
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into() }
+    }
+
+    /// Deletes the cache with all its entries
+    pub async fn clear(&self) -> Result<(), Error> {
+        JsFuture::from(caches()?.delete(&self.name))
+            .await
+            .map_err(Error::js_error)?;
+
+        Ok(())
+    }
+}
+
+impl CacheBackend for BrowserCache {
+    fn get(&self, key: &str) -> CacheFuture<Option<CacheEntry>> {
+        let name = self.name.clone();
+        let url = format!("{CACHE_URL}{key}");
+
+        Box::pin(async move {
+            let cache = open(&name).await?;
+            let response = JsFuture::from(cache.match_with_str(&url))
+                .await
+                .map_err(Error::js_error)?;
+
+            if response.is_undefined() {
+                return Ok(None);
+            }
+
+            let response: web_sys::Response = response.unchecked_into();
+            let expires_at = expires_at(&response);
+
+            if expires_at <= now() {
+                JsFuture::from(cache.delete_with_str(&url))
+                    .await
+                    .map_err(Error::js_error)?;
+                return Ok(None);
+            }
+
+            let mut headers = HeaderMap::new();
+            let mut trailers = HeaderMap::new();
+
+            for (name, value) in response_headers(&response)?.iter() {
+                if name == EXPIRES_AT {
+                    continue;
+                } else if let Some(trailer) = name.as_str().strip_prefix(TRAILER_PREFIX) {
+                    trailers.append(HeaderName::from_bytes(trailer.as_bytes())?, value.clone());
+                } else {
+                    headers.append(name, value.clone());
+                }
+            }
+
+            let body = JsFuture::from(response.array_buffer().map_err(Error::js_error)?)
+                .await
+                .map_err(Error::js_error)?;
+
+            Ok(Some(CacheEntry {
+                headers,
+                body: Bytes::from(Uint8Array::new(&body).to_vec()),
+                trailers,
+                expires_at,
+            }))
+        })
+    }
+
+    fn put(&self, key: &str, entry: CacheEntry) -> CacheFuture<()> {
+        let name = self.name.clone();
+        let url = format!("{CACHE_URL}{key}");
+
+        Box::pin(async move {
+            let headers = Headers::new().map_err(Error::js_error)?;
+            for (name, value) in entry.headers.iter() {
+                headers
+                    .append(name.as_str(), value.to_str()?)
+                    .map_err(Error::js_error)?;
+            }
+            for (name, value) in entry.trailers.iter() {
+                headers
+                    .append(&format!("{TRAILER_PREFIX}{name}"), value.to_str()?)
+                    .map_err(Error::js_error)?;
+            }
+            headers
+                .set(EXPIRES_AT, &entry.expires_at.to_string())
+                .map_err(Error::js_error)?;
+
+            let init = ResponseInit::new();
+            init.set_headers(headers.as_ref());
+
+            let body = Uint8Array::from(entry.body.as_ref());
+            let response =
+                web_sys::Response::new_with_opt_buffer_source_and_init(Some(&body), &init)
+                    .map_err(Error::js_error)?;
+
+            let cache = open(&name).await?;
+            JsFuture::from(cache.put_with_str(&url, &response))
+                .await
+                .map_err(Error::js_error)?;
+
+            prune(&cache).await
+        })
+    }
+
+    fn remove_prefix(&self, prefix: &str) -> CacheFuture<()> {
+        let name = self.name.clone();
+        let prefix = format!("{CACHE_URL}{prefix}");
+
+        Box::pin(async move {
+            let cache = open(&name).await?;
+            let requests: Array = JsFuture::from(cache.keys())
+                .await
+                .map_err(Error::js_error)?
+                .unchecked_into();
+
+            for request in requests.iter() {
+                let request: web_sys::Request = request.unchecked_into();
+
+                if request.url().starts_with(&prefix) {
+                    JsFuture::from(cache.delete_with_request(&request))
+                        .await
+                        .map_err(Error::js_error)?;
+                }
+            }
+
+            Ok(())
+        })
+    }
+}
+
+/// Deletes all expired entries from `cache`
+async fn prune(cache: &Cache) -> Result<(), Error> {
+    let now = now();
+    let requests: Array = JsFuture::from(cache.keys())
+        .await
+        .map_err(Error::js_error)?
+        .unchecked_into();
+
+    for request in requests.iter() {
+        let request: web_sys::Request = request.unchecked_into();
+        let response = JsFuture::from(cache.match_with_request(&request))
+            .await
+            .map_err(Error::js_error)?;
+
+        if !response.is_undefined() && expires_at(response.unchecked_ref()) <= now {
+            JsFuture::from(cache.delete_with_request(&request))
+                .await
+                .map_err(Error::js_error)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the expiry time of a stored entry (entries without a valid expiry time are expired)
+fn expires_at(response: &web_sys::Response) -> f64 {
+    response
+        .headers()
+        .get(EXPIRES_AT)
+        .ok()
+        .flatten()
+        .and_then(|expires_at| expires_at.parse().ok())
+        .unwrap_or_default()
+}
+
+/// Returns `caches` of the current window or worker
+fn caches() -> Result<CacheStorage, Error> {
+    let caches =
+        Reflect::get(&js_sys::global(), &JsValue::from_str("caches")).map_err(Error::js_error)?;
+
+    if caches.is_undefined() {
+        return Err(Error::JsError("Cache Storage is not available".to_string()));
+    }
+
+    Ok(caches.unchecked_into())
+}
+
+/// Opens the cache named `name`
+async fn open(name: &str) -> Result<Cache, Error> {
+    let cache = JsFuture::from(caches()?.open(name))
+        .await
+        .map_err(Error::js_error)?;
+
+    Ok(cache.unchecked_into())
+}
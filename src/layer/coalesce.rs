@@ -0,0 +1,387 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures_util::future::{FutureExt, WeakShared};
+use http::{
+    Extensions, HeaderMap, HeaderName, HeaderValue, Request, Response, StatusCode, Version,
+};
+use http_body_util::BodyExt;
+use tonic::body::Body;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{Error, ResponseBody, layer::BufferedRequest};
+
+type ResponseFuture = Pin<Box<dyn Future<Output = Result<BufferedResponse, Rc<Error>>>>>;
+
+type InFlight = Rc<RefCell<HashMap<Key, WeakShared<ResponseFuture>>>>;
+
+/// Layer which shares a single in-flight call between identical unary calls
+///
+/// Calls to registered methods are identical if they have the same path, request body and metadata. While a call is in
+/// flight, identical calls wait for its response instead of sending another request. The response is buffered and
+/// every waiting caller receives a copy of it. Errors (including errors while reading the response body) are copied
+/// for every caller as well, so that e.g. network errors stay [`Error::NetworkError`]. The call is cancelled once all
+/// waiting callers are dropped.
+///
+/// Only unary methods may be registered, as the whole response is buffered before it is handed over to callers.
+#[derive(Debug, Clone, Default)]
+pub struct CoalesceLayer {
+    methods: HashSet<String>,
+}
+
+impl CoalesceLayer {
+    /// Creates a new coalesce layer without any registered methods
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Registers a unary method (e.g. `/echo.Echo/Echo`) whose identical calls are coalesced
+    pub fn method(mut self, path: impl Into<String>) -> Self {
+        self.methods.insert(path.into());
+        self
+    }
+}
+
+impl<S> Layer<S> for CoalesceLayer {
+    type Service = CoalesceService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CoalesceService {
+            inner,
+            methods: Rc::new(self.methods.clone()),
+            in_flight: Default::default(),
+        }
+    }
+}
+
+/// Service created by [`CoalesceLayer`]
+///
+/// Clones of the service share in-flight calls.
+#[derive(Clone)]
+pub struct CoalesceService<S> {
+    inner: S,
+    methods: Rc<HashSet<String>>,
+    in_flight: InFlight,
+}
+
+impl<S: fmt::Debug> fmt::Debug for CoalesceService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CoalesceService")
+            .field("inner", &self.inner)
+            .field("methods", &self.methods)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> Service<Request<Body>> for CoalesceService<S>
+where
+    S: Service<Request<Body>, Response = Response<ResponseBody>, Error = Error> + Clone + 'static,
+{
+    type Response = Response<ResponseBody>;
+
+    type Error = Error;
+
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // Use the service which was driven to readiness and leave a clone in its place
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        if !self.methods.contains(request.uri().path()) {
+            return Box::pin(async move { inner.call(request).await });
+        }
+
+        let in_flight = self.in_flight.clone();
+
+        Box::pin(async move {
+            let path = request.uri().path().to_owned();
+            let request = BufferedRequest::new(request).await?;
+            let key = Key::new(path, &request);
+
+            let existing = in_flight.borrow().get(&key).and_then(WeakShared::upgrade);
+
+            let shared = match existing {
+                Some(shared) => shared,
+                None => {
+                    let guard = InFlightGuard {
+                        in_flight: in_flight.clone(),
+                        key: key.clone(),
+                    };
+
+                    let future: ResponseFuture = Box::pin(async move {
+                        let _guard = guard;
+
+                        let result = match inner.call(request.to_request()).await {
+                            Ok(response) => BufferedResponse::new(response).await,
+                            Err(error) => Err(error),
+                        };
+
+                        result.map_err(Rc::new)
+                    });
+
+                    let shared = future.shared();
+                    if let Some(weak) = shared.downgrade() {
+                        in_flight.borrow_mut().insert(key, weak);
+                    }
+
+                    shared
+                }
+            };
+
+            match shared.await {
+                Ok(response) => Ok(response.to_response()),
+                Err(error) => Err(error.share()),
+            }
+        })
+    }
+}
+
+/// Removes an in-flight call from the map when the call completes or all of its callers are dropped
+struct InFlightGuard {
+    in_flight: InFlight,
+    key: Key,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.in_flight.borrow_mut().remove(&self.key);
+    }
+}
+
+/// Identity of a call
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    path: String,
+    body: Bytes,
+    metadata: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl Key {
+    fn new(path: String, request: &BufferedRequest) -> Self {
+        let mut metadata: Vec<_> = request
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        metadata.sort_by(|(a_name, a_value), (b_name, b_value)| {
+            (a_name.as_str(), a_value.as_bytes()).cmp(&(b_name.as_str(), b_value.as_bytes()))
+        });
+
+        Self {
+            path,
+            body: request.body().clone(),
+            metadata,
+        }
+    }
+}
+
+/// Fully buffered response which can be handed over to multiple callers
+#[derive(Clone)]
+struct BufferedResponse {
+    status: StatusCode,
+    version: Version,
+    headers: HeaderMap,
+    extensions: Extensions,
+    body: Bytes,
+    trailers: HeaderMap,
+}
+
+impl BufferedResponse {
+    async fn new(response: Response<ResponseBody>) -> Result<Self, Error> {
+        let (parts, body) = response.into_parts();
+        let collected = body.collect().await?;
+        let trailers = collected.trailers().cloned().unwrap_or_default();
+
+        Ok(Self {
+            status: parts.status,
+            version: parts.version,
+            headers: parts.headers,
+            extensions: parts.extensions,
+            body: collected.to_bytes(),
+            trailers,
+        })
+    }
+
+    fn to_response(&self) -> Response<ResponseBody> {
+        let body = ResponseBody::from_message(self.body.as_ref().into(), self.trailers.clone());
+        let mut response = Response::new(body);
+
+        *response.status_mut() = self.status;
+        *response.version_mut() = self.version;
+        *response.headers_mut() = self.headers.clone();
+        *response.extensions_mut() = self.extensions.clone();
+
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{pin::pin, rc::Rc, task::Waker, time::Duration};
+
+    use futures_util::future::{join, poll_fn};
+    use http_body_util::Full;
+
+    use super::*;
+    use crate::{
+        ClientBuilder,
+        fetch::{FetchBackend, FetchBody, FetchFuture},
+        mock_fetch::{MockFetch, data_frame, trailer_frame},
+        options::FetchOptions,
+    };
+
+    /// Backend which answers after yielding once, so that calls overlap
+    struct Yielding(Rc<MockFetch>);
+
+    impl FetchBackend for Yielding {
+        fn fetch(
+            &self,
+            request: Request<FetchBody>,
+            options: &FetchOptions,
+            timeout: Option<Duration>,
+        ) -> FetchFuture {
+            let response = self.0.fetch(request, options, timeout);
+            let mut yielded = false;
+
+            Box::pin(async move {
+                poll_fn(|cx| {
+                    if yielded {
+                        Poll::Ready(())
+                    } else {
+                        yielded = true;
+                        cx.waker().wake_by_ref();
+                        Poll::Pending
+                    }
+                })
+                .await;
+
+                response.await
+            })
+        }
+    }
+
+    fn request(path: &'static str, message: &'static [u8]) -> Request<Body> {
+        let mut request = Request::new(Body::new(Full::new(Bytes::from_static(message))));
+        *request.uri_mut() = path.parse().unwrap();
+        request
+    }
+
+    #[test]
+    fn test_coalesce_layer() {
+        let vals = [
+            ("/echo.Echo/Echo", &b"hello"[..], 1),
+            ("/echo.Echo/Echo", &b"world"[..], 2),
+            ("/echo.Echo/EchoStream", &b"hello"[..], 2),
+        ];
+
+        for (path, message, fetches) in vals.iter() {
+            let fetch = Rc::new(
+                MockFetch::grpc_web()
+                    .chunk(data_frame(b"echo"))
+                    .chunk(trailer_frame("grpc-status: 0\r\n")),
+            );
+
+            let mut client = ClientBuilder::new("http://localhost:50051".to_string())
+                .backend(Yielding(fetch.clone()))
+                .layer(CoalesceLayer::new().method("/echo.Echo/Echo"))
+                .build()
+                .unwrap();
+            let mut other = client.clone();
+
+            let mut calls = pin!(join(
+                client.call(request(path, b"hello")),
+                other.call(request(path, message)),
+            ));
+            let mut cx = Context::from_waker(Waker::noop());
+            let (first, second) = loop {
+                if let Poll::Ready(responses) = calls.as_mut().poll(&mut cx) {
+                    break responses;
+                }
+            };
+
+            assert_eq!(fetch.requests().len(), *fetches);
+
+            if *fetches == 1 {
+                for response in [first, second] {
+                    let collected = response
+                        .unwrap()
+                        .into_body()
+                        .collect()
+                        .now_or_never()
+                        .unwrap()
+                        .unwrap();
+
+                    assert_eq!(collected.trailers().unwrap()["grpc-status"], "0");
+                    assert_eq!(collected.to_bytes(), data_frame(b"echo"));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_coalesce_error() {
+        // JS values (e.g. of network errors) can not be copied in native tests
+        let fetch = Rc::new(MockFetch::grpc_web().error(Error::MalformedResponse));
+
+        let mut client = ClientBuilder::new("http://localhost:50051".to_string())
+            .backend(Yielding(fetch.clone()))
+            .layer(CoalesceLayer::new().method("/echo.Echo/Echo"))
+            .build()
+            .unwrap();
+        let mut other = client.clone();
+
+        let mut calls = pin!(join(
+            client.call(request("/echo.Echo/Echo", b"hello")),
+            other.call(request("/echo.Echo/Echo", b"hello")),
+        ));
+        let mut cx = Context::from_waker(Waker::noop());
+        let (first, second) = loop {
+            if let Poll::Ready(responses) = calls.as_mut().poll(&mut cx) {
+                break responses;
+            }
+        };
+
+        // Every caller receives the original error instead of a status
+        assert_eq!(fetch.requests().len(), 1);
+        assert!(matches!(first, Err(Error::MalformedResponse)));
+        assert!(matches!(second, Err(Error::MalformedResponse)));
+    }
+
+    #[test]
+    fn test_coalesce_cancelled() {
+        let fetch = Rc::new(
+            MockFetch::grpc_web()
+                .chunk(data_frame(b"echo"))
+                .chunk(trailer_frame("grpc-status: 0\r\n")),
+        );
+
+        let mut client = ClientBuilder::new("http://localhost:50051".to_string())
+            .backend(Yielding(fetch.clone()))
+            .layer(CoalesceLayer::new().method("/echo.Echo/Echo"))
+            .build()
+            .unwrap();
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut call = client.call(request("/echo.Echo/Echo", b"hello"));
+        assert!(call.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(client.in_flight.borrow().len(), 1);
+
+        // Dropping the only caller cancels the call and removes it from in-flight calls
+        drop(call);
+        assert!(client.in_flight.borrow().is_empty());
+    }
+}
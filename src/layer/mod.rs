@@ -0,0 +1,21 @@
+//! `tower` layers for [`Client`](crate::Client)
+//!
+//! Layers are added using [`ClientBuilder::layer`](crate::ClientBuilder::layer).
+mod auth;
+mod buffered_request;
+mod cache;
+mod coalesce;
+mod outbox;
+
+pub use self::{
+    auth::{AuthLayer, AuthService},
+    buffered_request::BufferedRequest,
+    cache::{
+        BrowserCache, CacheBackend, CacheEntry, CacheFuture, CacheLayer, CacheService, MemoryCache,
+    },
+    coalesce::{CoalesceLayer, CoalesceService},
+    outbox::{
+        IndexedDbStore, MemoryStore, OUTBOX_ID, OutboxFuture, OutboxLayer, OutboxOutcome,
+        OutboxRequest, OutboxService, OutboxStore,
+    },
+};
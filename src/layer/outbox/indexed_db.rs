@@ -0,0 +1,284 @@
+use std::{
+    cell::{OnceCell, RefCell},
+    rc::Rc,
+};
+
+use bytes::Bytes;
+use http::{HeaderMap, HeaderName, HeaderValue};
+use js_sys::{Array, Object, Promise, Reflect, Uint8Array};
+use wasm_bindgen::{JsCast, JsValue, prelude::Closure};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    IdbCursorWithValue, IdbDatabase, IdbFactory, IdbObjectStore, IdbObjectStoreParameters,
+    IdbRequest, IdbTransactionMode,
+};
+
+use super::{OutboxFuture, OutboxRequest, OutboxStore};
+use crate::Error;
+
+/// Name of the object store containing queued requests
+const STORE: &str = "outbox";
+
+/// [`OutboxStore`] persisting queued requests in IndexedDB, so that they survive page reloads
+///
+/// Requests are stored with their metadata, except for credentials removed by
+/// [`OutboxLayer`](super::OutboxLayer) before queueing.
+///
+/// The database is opened on first use and the connection is shared by all clones of the store. The connection is
+/// closed when the last clone is dropped, or when another page upgrades or deletes the database (in which case it is
+/// opened again on next use).
+#[derive(Debug, Clone)]
+pub struct IndexedDbStore {
+    name: String,
+    connection: Rc<Connection>,
+}
+
+#[derive(Debug, Default)]
+struct Connection {
+    database: RefCell<Option<IdbDatabase>>,
+    on_version_change: OnceCell<Closure<dyn FnMut()>>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if let Some(database) = self.database.get_mut().take() {
+            database.close();
+        }
+    }
+}
+
+impl IndexedDbStore {
+    /// Creates a new store using the IndexedDB database named `name`
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            connection: Default::default(),
+        }
+    }
+
+    /// Returns the open connection to the database, opening it if needed
+    async fn database(&self) -> Result<IdbDatabase, Error> {
+        if let Some(database) = self.connection.database.borrow().clone() {
+            return Ok(database);
+        }
+
+        let database = open(&self.name).await?;
+
+        // Another call may have opened the database in the meantime
+        if let Some(cached) = self.connection.database.borrow().clone() {
+            database.close();
+            return Ok(cached);
+        }
+
+        // Closing the connection unblocks upgrades and deletions of the database by other pages
+        let on_version_change = self.connection.on_version_change.get_or_init(|| {
+            let connection = Rc::downgrade(&self.connection);
+            Closure::new(move || {
+                let database = connection
+                    .upgrade()
+                    .and_then(|connection| connection.database.borrow_mut().take());
+
+                if let Some(database) = database {
+                    database.close();
+                }
+            })
+        });
+        database.set_onversionchange(Some(on_version_change.as_ref().unchecked_ref()));
+
+        *self.connection.database.borrow_mut() = Some(database.clone());
+        Ok(database)
+    }
+
+    async fn object_store(&self, mode: IdbTransactionMode) -> Result<IdbObjectStore, Error> {
+        self.database()
+            .await?
+            .transaction_with_str_and_mode(STORE, mode)
+            .map_err(Error::js_error)?
+            .object_store(STORE)
+            .map_err(Error::js_error)
+    }
+}
+
+impl OutboxStore for IndexedDbStore {
+    fn push(&self, request: OutboxRequest) -> OutboxFuture<u64> {
+        let this = self.clone();
+
+        Box::pin(async move {
+            let value = to_js(&request)?;
+            let store = this.object_store(IdbTransactionMode::Readwrite).await?;
+            let id = request_result(&store.add(&value).map_err(Error::js_error)?).await?;
+
+            id.as_f64()
+                .map(|id| id as u64)
+                .ok_or_else(|| Error::JsError("invalid IndexedDB key".to_string()))
+        })
+    }
+
+    fn peek(&self) -> OutboxFuture<Option<(u64, OutboxRequest)>> {
+        let this = self.clone();
+
+        Box::pin(async move {
+            let store = this.object_store(IdbTransactionMode::Readonly).await?;
+            let cursor = request_result(&store.open_cursor().map_err(Error::js_error)?).await?;
+
+            if cursor.is_null() {
+                return Ok(None);
+            }
+
+            let cursor: IdbCursorWithValue = cursor.unchecked_into();
+            let id = cursor
+                .key()
+                .map_err(Error::js_error)?
+                .as_f64()
+                .ok_or_else(|| Error::JsError("invalid IndexedDB key".to_string()))?;
+            let request = from_js(&cursor.value().map_err(Error::js_error)?)?;
+
+            Ok(Some((id as u64, request)))
+        })
+    }
+
+    fn remove(&self, id: u64) -> OutboxFuture<()> {
+        let this = self.clone();
+
+        Box::pin(async move {
+            let store = this.object_store(IdbTransactionMode::Readwrite).await?;
+            let key = JsValue::from_f64(id as f64);
+            request_result(&store.delete(&key).map_err(Error::js_error)?).await?;
+
+            Ok(())
+        })
+    }
+}
+
+/// Opens the database named `name` using `indexedDB` of the current window or worker
+async fn open(name: &str) -> Result<IdbDatabase, Error> {
+    let factory = Reflect::get(&js_sys::global(), &JsValue::from_str("indexedDB"))
+        .map_err(Error::js_error)?;
+
+    if factory.is_undefined() {
+        return Err(Error::JsError("IndexedDB is not available".to_string()));
+    }
+
+    let request = factory
+        .unchecked_into::<IdbFactory>()
+        .open_with_u32(name, 1)
+        .map_err(Error::js_error)?;
+
+    let upgrade_request = request.clone();
+    let on_upgrade = Closure::<dyn FnMut()>::new(move || {
+        if let Ok(database) = upgrade_request.result() {
+            let parameters = IdbObjectStoreParameters::new();
+            parameters.set_auto_increment(true);
+
+            let _ = database
+                .unchecked_into::<IdbDatabase>()
+                .create_object_store_with_optional_parameters(STORE, &parameters);
+        }
+    });
+    request.set_onupgradeneeded(Some(on_upgrade.as_ref().unchecked_ref()));
+
+    let promise = Promise::new(&mut |resolve, reject| {
+        request.set_onsuccess(Some(&resolve));
+        request.set_onerror(Some(&reject));
+        request.set_onblocked(Some(&reject));
+    });
+    let settled = JsFuture::from(promise).await;
+
+    // `upgradeneeded` always fires before the request settles, so the closure is not needed anymore
+    request.set_onupgradeneeded(None);
+    drop(on_upgrade);
+
+    match settled {
+        Ok(_) => Ok(request.result().map_err(Error::js_error)?.unchecked_into()),
+        Err(event) if is_blocked(&event) => {
+            // A blocked request may still succeed later, in which case the connection is closed right away
+            let blocked_request = request.clone();
+            let on_success = Closure::once_into_js(move || {
+                if let Ok(database) = blocked_request.result() {
+                    database.unchecked_into::<IdbDatabase>().close();
+                }
+            });
+            request.set_onsuccess(Some(on_success.unchecked_ref()));
+
+            Err(Error::JsError(
+                "IndexedDB database is blocked by another connection".to_string(),
+            ))
+        }
+        Err(_) => {
+            let error = request.error().ok().flatten().map(JsValue::from);
+            Err(Error::js_error(error.unwrap_or_default()))
+        }
+    }
+}
+
+/// Returns `true` if `event` is the `blocked` event of an open request
+fn is_blocked(event: &JsValue) -> bool {
+    Reflect::get(event, &JsValue::from_str("type"))
+        .ok()
+        .and_then(|kind| kind.as_string())
+        .is_some_and(|kind| kind == "blocked")
+}
+
+/// Waits for `request` to finish and returns its result
+async fn request_result(request: &IdbRequest) -> Result<JsValue, Error> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        request.set_onsuccess(Some(&resolve));
+        request.set_onerror(Some(&reject));
+    });
+
+    if JsFuture::from(promise).await.is_err() {
+        let error = request.error().ok().flatten().map(JsValue::from);
+        return Err(Error::js_error(error.unwrap_or_default()));
+    }
+
+    request.result().map_err(Error::js_error)
+}
+
+fn to_js(request: &OutboxRequest) -> Result<JsValue, Error> {
+    let metadata = Array::new();
+    for (name, value) in request.metadata.iter() {
+        metadata.push(&Array::of2(
+            &JsValue::from_str(name.as_str()),
+            &JsValue::from_str(value.to_str()?),
+        ));
+    }
+
+    let object = Object::new();
+    for (key, value) in [
+        ("path", JsValue::from_str(&request.path)),
+        ("metadata", metadata.into()),
+        ("body", Uint8Array::from(request.body.as_ref()).into()),
+    ] {
+        Reflect::set(&object, &JsValue::from_str(key), &value).map_err(Error::js_error)?;
+    }
+
+    Ok(object.into())
+}
+
+fn from_js(value: &JsValue) -> Result<OutboxRequest, Error> {
+    let get = |key: &str| Reflect::get(value, &JsValue::from_str(key)).map_err(Error::js_error);
+
+    let path = get("path")?
+        .as_string()
+        .ok_or_else(|| Error::JsError("invalid path of queued request".to_string()))?;
+
+    let mut metadata = HeaderMap::new();
+    for pair in get("metadata")?.unchecked_into::<Array>().iter() {
+        let pair: Array = pair.unchecked_into();
+
+        if let (Some(name), Some(value)) = (pair.get(0).as_string(), pair.get(1).as_string()) {
+            metadata.append(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_str(&value)?,
+            );
+        }
+    }
+
+    let body = Bytes::from(get("body")?.unchecked_into::<Uint8Array>().to_vec());
+
+    Ok(OutboxRequest {
+        path,
+        metadata,
+        body,
+    })
+}
@@ -0,0 +1,55 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use super::{OutboxFuture, OutboxRequest, OutboxStore};
+
+/// In-memory [`OutboxStore`] whose queue is lost when the page is reloaded
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    requests: BTreeMap<u64, OutboxRequest>,
+    next_id: u64,
+}
+
+impl MemoryStore {
+    /// Creates a new empty store
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn with_state<T>(&self, f: impl FnOnce(&mut State) -> T) -> T {
+        f(&mut self.state.lock().unwrap_or_else(|error| error.into_inner()))
+    }
+}
+
+impl OutboxStore for MemoryStore {
+    fn push(&self, request: OutboxRequest) -> OutboxFuture<u64> {
+        let id = self.with_state(|state| {
+            state.next_id += 1;
+            state.requests.insert(state.next_id, request);
+            state.next_id
+        });
+
+        Box::pin(async move { Ok(id) })
+    }
+
+    fn peek(&self) -> OutboxFuture<Option<(u64, OutboxRequest)>> {
+        let first = self.with_state(|state| {
+            state
+                .requests
+                .first_key_value()
+                .map(|(id, request)| (*id, request.clone()))
+        });
+
+        Box::pin(async move { Ok(first) })
+    }
+
+    fn remove(&self, id: u64) -> OutboxFuture<()> {
+        self.with_state(|state| state.requests.remove(&id));
+
+        Box::pin(async { Ok(()) })
+    }
+}
@@ -0,0 +1,543 @@
+mod indexed_db;
+mod memory;
+mod network;
+
+use std::{
+    cell::Cell,
+    collections::HashSet,
+    fmt,
+    future::{Future, poll_fn},
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use http::{HeaderMap, HeaderName, Request, Response, header::AUTHORIZATION};
+use http_body_util::{BodyExt, Full};
+use tonic::{Status, body::Body, metadata::MetadataMap};
+use tower_layer::Layer;
+use tower_service::Service;
+
+pub use self::{indexed_db::IndexedDbStore, memory::MemoryStore};
+use crate::{Error, ResponseBody, layer::BufferedRequest};
+
+/// Metadata key of the status returned for queued calls, which contains the ID of the queued request
+pub const OUTBOX_ID: &str = "x-outbox-id";
+
+/// Future returned by [`OutboxStore`] methods
+pub type OutboxFuture<T> = Pin<Box<dyn Future<Output = Result<T, Error>>>>;
+
+type OutcomeFn = dyn Fn(OutboxOutcome);
+
+/// Persistent queue of requests used by [`OutboxLayer`]
+pub trait OutboxStore {
+    /// Appends `request` to the queue and returns its ID (IDs increase in queue order)
+    fn push(&self, request: OutboxRequest) -> OutboxFuture<u64>;
+
+    /// Returns the oldest request in the queue with its ID, if any
+    fn peek(&self) -> OutboxFuture<Option<(u64, OutboxRequest)>>;
+
+    /// Removes the request with `id` from the queue
+    fn remove(&self, id: u64) -> OutboxFuture<()>;
+}
+
+/// Unary request queued for replay
+#[derive(Debug, Clone)]
+pub struct OutboxRequest {
+    /// Path of the method (e.g. `/echo.Echo/Echo`)
+    pub path: String,
+    /// Request metadata
+    pub metadata: HeaderMap,
+    /// Request body containing the encoded message frame
+    pub body: Bytes,
+}
+
+impl OutboxRequest {
+    fn to_request(&self) -> Result<Request<Body>, Error> {
+        let mut request = Request::new(Body::new(Full::new(self.body.clone())));
+
+        *request.method_mut() = http::Method::POST;
+        *request.uri_mut() = self.path.parse().map_err(http::Error::from)?;
+        *request.headers_mut() = self.metadata.clone();
+
+        Ok(request)
+    }
+}
+
+/// Outcome of a replayed request
+#[derive(Debug, Clone)]
+pub struct OutboxOutcome {
+    /// ID of the request (as returned in the [`OUTBOX_ID`] metadata of the status of the original call)
+    pub id: u64,
+    /// Path of the method (e.g. `/echo.Echo/Echo`)
+    pub path: String,
+    /// Final status of the call (`Code::Ok` if the call succeeded)
+    pub status: Status,
+}
+
+/// Layer which queues calls of registered unary methods while offline and replays them once back online
+///
+/// A call is queued in an [`OutboxStore`] (e.g. [`IndexedDbStore`], which survives page reloads) instead of being sent
+/// when `navigator.onLine` is `false`, when sending it fails with [`Error::NetworkError`], or when older requests are
+/// still queued (so that calls never overtake queued ones). Queued calls fail with `Code::Unavailable` and a status
+/// whose [`OUTBOX_ID`] metadata contains the ID of the queued request.
+///
+/// Queued requests are replayed in order when the `online` event fires (and on the first call, to replay requests left
+/// from a previous session), when a call is queued behind older requests while online, or when
+/// [`OutboxService::replay`] is called. Services created by the same layer share the `online` listener and never replay
+/// at the same time, so each queued request is sent once. Replaying stops at the first request failing with a network
+/// error, which stays at the front of the queue. Outcomes of replayed requests are reported to the callback set with
+/// [`OutboxLayer::on_outcome`].
+///
+/// Queued requests are persisted without credentials: the `authorization` header (and headers added with
+/// [`OutboxLayer::strip`]) is removed before queueing, as tokens are likely stale by the time requests are replayed.
+/// Add [`AuthLayer`](crate::layer::AuthLayer) after this layer, so that replayed requests pass through it and get a
+/// fresh token.
+#[derive(Clone)]
+pub struct OutboxLayer {
+    store: Rc<dyn OutboxStore>,
+    methods: HashSet<String>,
+    strip: Vec<HeaderName>,
+    on_outcome: Option<Rc<OutcomeFn>>,
+    shared: Rc<Shared>,
+}
+
+/// State shared by all services created by an [`OutboxLayer`]
+#[derive(Default)]
+struct Shared {
+    replaying: Cell<bool>,
+    pending: Cell<bool>,
+    listening: Cell<bool>,
+}
+
+/// Marks the queue as being replayed until dropped, even if replaying is cancelled
+struct Replaying<'a>(&'a Cell<bool>);
+
+impl Drop for Replaying<'_> {
+    fn drop(&mut self) {
+        self.0.set(false);
+    }
+}
+
+impl OutboxLayer {
+    /// Creates a new outbox layer queueing requests in `store`
+    pub fn new(store: impl OutboxStore + 'static) -> Self {
+        Self {
+            store: Rc::new(store),
+            methods: HashSet::new(),
+            strip: vec![AUTHORIZATION],
+            on_outcome: None,
+            shared: Default::default(),
+        }
+    }
+
+    /// Registers a unary method (e.g. `/echo.Echo/Echo`) whose calls are queued while offline
+    pub fn method(mut self, path: impl Into<String>) -> Self {
+        self.methods.insert(path.into());
+        self
+    }
+
+    /// Adds a header which is removed from requests before they are queued (defaults to `authorization` only)
+    pub fn strip(mut self, header_name: HeaderName) -> Self {
+        self.strip.push(header_name);
+        self
+    }
+
+    /// Sets the callback receiving outcomes of replayed requests
+    pub fn on_outcome(mut self, on_outcome: impl Fn(OutboxOutcome) + 'static) -> Self {
+        self.on_outcome = Some(Rc::new(on_outcome));
+        self
+    }
+}
+
+impl fmt::Debug for OutboxLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OutboxLayer")
+            .field("methods", &self.methods)
+            .field("strip", &self.strip)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> Layer<S> for OutboxLayer {
+    type Service = OutboxService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        OutboxService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// Service created by [`OutboxLayer`]
+#[derive(Debug, Clone)]
+pub struct OutboxService<S> {
+    inner: S,
+    layer: OutboxLayer,
+}
+
+impl<S> OutboxService<S>
+where
+    S: Service<Request<Body>, Response = Response<ResponseBody>, Error = Error> + Clone + 'static,
+{
+    /// Replays queued requests in order until the queue is empty or a request fails with a network error
+    ///
+    /// If requests are already being replayed, the queue is checked again once the replay in progress is done.
+    pub async fn replay(&self) -> Result<(), Error> {
+        let shared = &self.layer.shared;
+
+        shared.pending.set(true);
+        if shared.replaying.replace(true) {
+            return Ok(());
+        }
+
+        let _replaying = Replaying(&shared.replaying);
+        while shared.pending.replace(false) {
+            self.replay_queue().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Registers the `online` listener of the layer, unless a service of the layer already did
+    fn listen(&self) {
+        if self.layer.shared.listening.replace(true) {
+            return;
+        }
+
+        let service = self.clone();
+        network::when_online(move || {
+            let service = service.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let _ = service.replay().await;
+            });
+        });
+    }
+
+    async fn replay_queue(&self) -> Result<(), Error> {
+        let mut inner = self.inner.clone();
+
+        while let Some((id, request)) = self.layer.store.peek().await? {
+            poll_fn(|cx| inner.poll_ready(cx)).await?;
+
+            let status = match inner.call(request.to_request()?).await {
+                Err(Error::NetworkError(_)) => return Ok(()),
+                Err(error) => Status::from(error),
+                Ok(response) => response_status(response).await,
+            };
+
+            self.layer.store.remove(id).await?;
+
+            if let Some(on_outcome) = &self.layer.on_outcome {
+                on_outcome(OutboxOutcome {
+                    id,
+                    path: request.path,
+                    status,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<S> Service<Request<Body>> for OutboxService<S>
+where
+    S: Service<Request<Body>, Response = Response<ResponseBody>, Error = Error> + Clone + 'static,
+{
+    type Response = Response<ResponseBody>;
+
+    type Error = Error;
+
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        self.listen();
+
+        // Use the service which was driven to readiness and leave a clone in its place
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        if !self.layer.methods.contains(request.uri().path()) {
+            return Box::pin(async move { inner.call(request).await });
+        }
+
+        let service = self.clone();
+
+        Box::pin(async move {
+            let store = &service.layer.store;
+            let path = request.uri().path().to_owned();
+            let request = BufferedRequest::new(request).await?;
+
+            let online = network::is_online();
+            let overtaking = online && store.peek().await?.is_some();
+
+            if online && !overtaking {
+                match inner.call(request.to_request()).await {
+                    Err(Error::NetworkError(_)) => {}
+                    result => return result,
+                }
+            }
+
+            let mut metadata = request.headers().clone();
+            for header_name in &service.layer.strip {
+                metadata.remove(header_name);
+            }
+
+            let id = store
+                .push(OutboxRequest {
+                    path,
+                    metadata,
+                    body: request.body().clone(),
+                })
+                .await?;
+
+            // Send the request in order after the older ones
+            if overtaking {
+                service.replay().await?;
+            }
+
+            let mut metadata = MetadataMap::new();
+            metadata.insert(OUTBOX_ID, id.into());
+
+            Err(Status::with_metadata(
+                tonic::Code::Unavailable,
+                "network unavailable, request queued for replay",
+                metadata,
+            )
+            .into())
+        })
+    }
+}
+
+/// Reads the final status of a replayed call from its response
+async fn response_status(response: Response<ResponseBody>) -> Status {
+    let headers = response.headers().clone();
+
+    match response.into_body().collect().await {
+        Ok(collected) => collected
+            .trailers()
+            .and_then(Status::from_header_map)
+            .or_else(|| Status::from_header_map(&headers))
+            .unwrap_or_else(|| Status::internal("missing grpc-status in response")),
+        Err(error) => Status::from(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, pin::pin, rc::Rc, task::Waker};
+
+    use futures_util::FutureExt;
+    use tonic::Code;
+
+    use super::*;
+    use crate::{
+        Client, ClientBuilder,
+        mock_fetch::{MockFetch, data_frame, trailer_frame},
+        options::ConcurrencyLimit,
+    };
+
+    fn queued_request() -> OutboxRequest {
+        OutboxRequest {
+            path: "/echo.Echo/Echo".to_string(),
+            metadata: HeaderMap::new(),
+            body: Bytes::from_static(b"hello"),
+        }
+    }
+
+    #[test]
+    fn test_replay() {
+        let vals = [
+            ("grpc-status: 0\r\n", Code::Ok),
+            (
+                "grpc-status: 5\r\ngrpc-message: missing\r\n",
+                Code::NotFound,
+            ),
+        ];
+
+        for (trailers, code) in vals.iter() {
+            let fetch = Rc::new(
+                MockFetch::grpc_web()
+                    .chunk(data_frame(b"echo"))
+                    .chunk(trailer_frame(trailers)),
+            );
+            let outcomes = Rc::new(RefCell::new(Vec::new()));
+
+            let layer = OutboxLayer::new(MemoryStore::new())
+                .method("/echo.Echo/Echo")
+                .on_outcome({
+                    let outcomes = outcomes.clone();
+                    move |outcome| outcomes.borrow_mut().push(outcome)
+                });
+            let service = ClientBuilder::new("http://localhost:50051".to_string())
+                .backend(fetch.clone())
+                .layer(layer.clone())
+                .build()
+                .unwrap();
+
+            let mut metadata = HeaderMap::new();
+            metadata.insert("x-session-id", "session-1".parse().unwrap());
+            let id = layer
+                .store
+                .push(OutboxRequest {
+                    path: "/echo.Echo/Echo".to_string(),
+                    metadata,
+                    body: Bytes::from_static(b"hello"),
+                })
+                .now_or_never()
+                .unwrap()
+                .unwrap();
+
+            service.replay().now_or_never().unwrap().unwrap();
+
+            let requests = fetch.requests();
+            assert_eq!(requests.len(), 1);
+            assert_eq!(
+                requests[0].request.uri(),
+                "http://localhost:50051/echo.Echo/Echo"
+            );
+            assert_eq!(requests[0].request.headers()["x-session-id"], "session-1");
+
+            let outcomes = outcomes.borrow();
+            assert_eq!(outcomes.len(), 1);
+            assert_eq!(outcomes[0].id, id);
+            assert_eq!(outcomes[0].status.code(), *code);
+
+            let queued = layer.store.peek().now_or_never().unwrap().unwrap();
+            assert!(queued.is_none());
+        }
+    }
+
+    #[test]
+    fn test_call_behind_queued() {
+        let fetch = Rc::new(MockFetch::grpc_web().chunk(trailer_frame("grpc-status: 0\r\n")));
+        let mut client = Client::new("http://localhost:50051".to_string());
+        client.with_backend(fetch.clone());
+
+        let layer = OutboxLayer::new(MemoryStore::new()).method("/echo.Echo/Echo");
+        let mut service = layer.layer(client);
+
+        let queued_id = layer
+            .store
+            .push(queued_request())
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+
+        let mut request = Request::new(Body::new(Full::new(Bytes::from_static(b"newer"))));
+        *request.uri_mut() = "/echo.Echo/Echo".parse().unwrap();
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, "Bearer stale".parse().unwrap());
+
+        // The call is queued behind the older request instead of overtaking it
+        let Some(Err(error)) = service.call(request).now_or_never() else {
+            panic!("call is not queued");
+        };
+        let status = Status::from(error);
+        assert_eq!(status.code(), Code::Unavailable);
+        let id: u64 = status
+            .metadata()
+            .get(OUTBOX_ID)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(id > queued_id);
+
+        // Both requests are replayed in order, without credentials
+        let requests = fetch.requests();
+        let bodies: Vec<_> = requests
+            .iter()
+            .map(|request| request.request.body().clone().unwrap())
+            .collect();
+        assert_eq!(bodies, [&b"hello"[..], &b"newer"[..]]);
+        assert!(!requests[1].request.headers().contains_key(AUTHORIZATION));
+    }
+
+    #[test]
+    fn test_replay_cancelled() {
+        let fetch = Rc::new(MockFetch::grpc_web().chunk(trailer_frame("grpc-status: 0\r\n")));
+        let mut client = Client::new("http://localhost:50051".to_string());
+        client
+            .with_backend(fetch.clone())
+            .with_concurrency_limit(ConcurrencyLimit::new().max_unary(1));
+
+        let layer = OutboxLayer::new(MemoryStore::new()).method("/echo.Echo/Echo");
+        let service = layer.layer(client.clone());
+
+        layer
+            .store
+            .push(queued_request())
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+
+        // Hold the only slot of the concurrency limit, so that replaying waits before sending the request
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut blocker = client.clone();
+        assert!(blocker.poll_ready(&mut cx).is_ready());
+
+        let mut replay = Box::pin(service.replay());
+        assert!(replay.as_mut().poll(&mut cx).is_pending());
+        drop(replay);
+        drop(blocker);
+
+        // Cancelling a replay does not prevent later ones
+        service.replay().now_or_never().unwrap().unwrap();
+        assert_eq!(fetch.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_replay_shared_between_services() {
+        let fetch = Rc::new(MockFetch::grpc_web().chunk(trailer_frame("grpc-status: 0\r\n")));
+        let mut client = Client::new("http://localhost:50051".to_string());
+        client
+            .with_backend(fetch.clone())
+            .with_concurrency_limit(ConcurrencyLimit::new().max_unary(1));
+
+        let layer = OutboxLayer::new(MemoryStore::new()).method("/echo.Echo/Echo");
+        let first = layer.layer(client.clone());
+        let second = layer.layer(client.clone());
+
+        layer
+            .store
+            .push(queued_request())
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+
+        // Hold the only slot of the concurrency limit, so that replaying waits before sending the request
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut blocker = client.clone();
+        assert!(blocker.poll_ready(&mut cx).is_ready());
+
+        let mut replay = pin!(first.replay());
+        assert!(replay.as_mut().poll(&mut cx).is_pending());
+
+        // Another service of the same layer does not replay the queue at the same time
+        second.replay().now_or_never().unwrap().unwrap();
+
+        drop(blocker);
+        assert!(matches!(replay.as_mut().poll(&mut cx), Poll::Ready(Ok(()))));
+
+        assert_eq!(fetch.requests().len(), 1);
+        assert!(
+            layer
+                .store
+                .peek()
+                .now_or_never()
+                .unwrap()
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,53 @@
+//! Detection of network connectivity using `navigator.onLine` and the `online` event
+
+#[cfg(target_arch = "wasm32")]
+mod imp {
+    use js_sys::{Function, Reflect};
+    use wasm_bindgen::{JsCast, JsValue, prelude::Closure};
+
+    /// Returns `false` if the browser reports to be offline
+    pub fn is_online() -> bool {
+        Reflect::get(&js_sys::global(), &JsValue::from_str("navigator"))
+            .and_then(|navigator| Reflect::get(&navigator, &JsValue::from_str("onLine")))
+            .ok()
+            .and_then(|online| online.as_bool())
+            .unwrap_or(true)
+    }
+
+    /// Calls `callback` now if online and whenever the `online` event fires
+    pub fn when_online(callback: impl Fn() + 'static) {
+        if is_online() {
+            callback();
+        }
+
+        let global = js_sys::global();
+        let Ok(add_event_listener) = Reflect::get(&global, &JsValue::from_str("addEventListener"))
+        else {
+            return;
+        };
+        let Some(add_event_listener) = add_event_listener.dyn_ref::<Function>() else {
+            return;
+        };
+
+        // The listener lives as long as the page or worker
+        let listener = Closure::<dyn Fn()>::new(callback);
+        let _ = add_event_listener.call2(
+            &global,
+            &JsValue::from_str("online"),
+            listener.as_ref().unchecked_ref(),
+        );
+        listener.forget();
+    }
+}
+
+/// Native unit tests are always online and never receive `online` events
+#[cfg(not(target_arch = "wasm32"))]
+mod imp {
+    pub fn is_online() -> bool {
+        true
+    }
+
+    pub fn when_online(_: impl Fn() + 'static) {}
+}
+
+pub use self::imp::{is_online, when_online};
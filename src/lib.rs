@@ -39,19 +39,205 @@
 //! target = "wasm32-unknown-unknown"
 //! ```
 //!
+//! ## Base URL
+//!
+//! `ClientBuilder` validates the base URL when building the client and returns `Error::InvalidBaseUrl` if it is not a
+//! valid `http` or `https` URL. Base URLs may contain a path prefix (e.g. `https://example.com/api/grpc`) and may be
+//! relative to the location of the current document or worker (e.g. `/grpc` for a same-origin server).
+//!
+//! ## Compression
+//!
+//! Messages are compressed and decompressed by `tonic`, so compression is enabled on the generated client using
+//! `send_compressed` and `accept_compressed`. Compression encodings are available behind cargo features of `tonic` (and
+//! of `tonic-web-wasm-client`, which forwards them): `gzip`, `deflate` and `zstd`. Compressed requests carry a
+//! `grpc-encoding` header, so cross-origin servers need to allow `grpc-encoding` and `grpc-accept-encoding` request
+//! headers.
+//!
+//! ```rust,ignore
+//! use tonic::codec::CompressionEncoding;
+//!
+//! let query_client = QueryClient::new(Client::new(base_url))
+//!     .send_compressed(CompressionEncoding::Gzip)
+//!     .accept_compressed(CompressionEncoding::Gzip);
+//! ```
+//!
+//! ## Connect protocol
+//!
+//! Servers speaking the [Connect protocol](https://connectrpc.com/docs/protocol) (e.g. `connect-go`) can be called using
+//! `Protocol::Connect`. Unary and streaming calls use different requests in Connect, which can not be told apart by the
+//! transport, so streaming methods need to be registered. Side-effect free unary methods can be sent as cacheable `GET`
+//! requests. Connect errors are reported as `tonic::Status` with the same code, message and details.
+//!
+//! ```rust,ignore
+//! use tonic_web_wasm_client::options::{ConnectOptions, FetchOptions, Protocol};
+//!
+//! let connect = ConnectOptions::new()
+//!     .streaming_method("/echo.Echo/EchoStream")
+//!     .get_method("/echo.Echo/Echo");
+//! let options = FetchOptions::new().protocol(Protocol::Connect(connect));
+//! let query_client = QueryClient::new(Client::new_with_options(base_url, options));
+//! ```
+//!
 //! ## Custom `Accept` header:
 //!
 //! This library allows you to set a custom `Accept` header for the requests. This can be useful if you need to specify
 //! a different content type for the responses. But, be aware that if you set a custom `Accept` header, the client may
 //! not be able to handle the response correctly.
+//!
+//! ## Response metadata
+//!
+//! Response headers are exposed as initial metadata through `tonic::Response::metadata()`. Browsers only expose
+//! [CORS-safelisted response headers](https://developer.mozilla.org/en-US/docs/Glossary/CORS-safelisted_response_header)
+//! to cross-origin requests, so the server needs to list custom metadata keys in `Access-Control-Expose-Headers`.
+//!
+//! Browsers combine repeated headers into a single comma separated value. Binary metadata (keys ending with `-bin`) is
+//! split back into separate values and normalized to the base64 format `tonic` expects, while ASCII metadata with
+//! repeated keys is delivered as a single comma separated value.
+//!
+//! ## Fetch backend
+//!
+//! Requests are sent using `fetch` of the global scope by default, which works in browser windows, web workers, Node.js
+//! and Deno. A custom JavaScript `fetch` function (e.g. an instrumented `fetch` provided by the application) can be used
+//! with `JsFetch::with_function`, and any other transport (e.g. a test double) can be plugged in by implementing the
+//! `FetchBackend` trait:
+//!
+//! ```rust,ignore
+//! use tonic_web_wasm_client::{Client, JsFetch};
+//!
+//! let mut client = Client::new(base_url);
+//! client.with_backend(JsFetch::with_function(instrumented_fetch));
+//! ```
+//!
+//! ## Middleware
+//!
+//! `Client` can be wrapped in [`tower`](https://crates.io/crates/tower) layers using `ClientBuilder::layer`. Layers added
+//! first see requests first. `layer::AuthLayer` sets an authorization header fetched by an async callback before each
+//! call and refreshes the token if the server responds with `Unauthenticated`. Calls of registered unary methods are
+//! replayed with the refreshed token; other calls are never buffered or replayed, so streams are not delayed. Custom layers
+//! which need to replay requests can buffer them using `layer::BufferedRequest`. `layer::CoalesceLayer` shares a single
+//! in-flight call between identical calls of registered unary methods (e.g. the same `GetConfig` call issued by several
+//! components at page load) and hands a copy of the buffered response over to every caller.
+//!
+//! ```rust,ignore
+//! use tonic_web_wasm_client::{ClientBuilder, layer::AuthLayer};
+//!
+//! let client = ClientBuilder::new(base_url)
+//!     .layer(AuthLayer::new(|refresh| fetch_token(refresh)).unary_method("/echo.Echo/Echo"))
+//!     .build()?;
+//! let query_client = QueryClient::new(client);
+//! ```
+//!
+//! ## Concurrency limit
+//!
+//! Browsers queue calls once all connections to an origin are in use, so a few long-lived streams can stall every other
+//! call. `ClientBuilder::concurrency_limit` (or `Client::with_concurrency_limit`) limits the number of calls in flight,
+//! with separate budgets for unary and streaming calls. Unary calls wait for a slot in `poll_ready`, so callers see
+//! backpressure. Streaming methods need to be registered and wait for a slot of their own, so they never take slots of
+//! unary calls. A call holds its slot until its response body is dropped.
+//!
+//! ```rust,ignore
+//! use tonic_web_wasm_client::{ClientBuilder, options::ConcurrencyLimit};
+//!
+//! let client = ClientBuilder::new(base_url)
+//!     .concurrency_limit(
+//!         ConcurrencyLimit::new()
+//!             .max_unary(4)
+//!             .max_streaming(2)
+//!             .streaming_method("/echo.Echo/EchoStream"),
+//!     )
+//!     .build()?;
+//! ```
+//!
+//! ## Response cache
+//!
+//! `FetchOptions::cache` only sets the HTTP cache mode, which has no effect on `POST` requests. `layer::CacheLayer` caches
+//! responses of registered idempotent unary methods by path, request body and `authorization` metadata (further metadata
+//! can be added with `CacheLayer::vary`) for a TTL, either in memory (`layer::MemoryCache`, evicting expired and then the
+//! least recently used entries) or in the browser's Cache Storage (`layer::BrowserCache`, deleting expired entries when
+//! they are looked up or a new entry is stored). Cached responses can be invalidated per method or key prefix, and
+//! `BrowserCache::clear` deletes all of them (e.g. on logout).
+//!
+//! ```rust,ignore
+//! use std::time::Duration;
+//!
+//! use tonic_web_wasm_client::{ClientBuilder, layer::{CacheLayer, MemoryCache}};
+//!
+//! let cache = CacheLayer::new(MemoryCache::new(100))
+//!     .method("/config.Config/GetConfig", Duration::from_secs(60));
+//! let client = ClientBuilder::new(base_url).layer(cache.clone()).build()?;
+//!
+//! // After updating the configuration
+//! cache.invalidate_method("/config.Config/GetConfig").await?;
+//! ```
+//!
+//! ## Offline outbox
+//!
+//! `layer::OutboxLayer` queues calls of registered unary methods (e.g. mutations) while `navigator.onLine` is `false` or
+//! when they fail with `Error::NetworkError`, and replays them in order when the `online` event fires. While requests are
+//! queued, new calls are queued behind them instead of overtaking them. Queued requests are stored in IndexedDB using
+//! `layer::IndexedDbStore`, so they survive page reloads. Queued calls fail with `Code::Unavailable` and carry the ID of
+//! the queued request in `x-outbox-id` metadata; the final status of each replayed request is reported to a callback.
+//! The `authorization` header is removed before requests are queued, so add `layer::AuthLayer` after the outbox layer to
+//! send replayed requests with a fresh token.
+//!
+//! ```rust,ignore
+//! use tonic_web_wasm_client::{ClientBuilder, layer::{AuthLayer, IndexedDbStore, OutboxLayer}};
+//!
+//! let outbox = OutboxLayer::new(IndexedDbStore::new("outbox"))
+//!     .method("/jobs.Jobs/UpdateJob")
+//!     .on_outcome(|outcome| log::info!("request {} finished with {:?}", outcome.id, outcome.status));
+//! let client = ClientBuilder::new(base_url)
+//!     .layer(outbox)
+//!     .layer(AuthLayer::new(|refresh| fetch_token(refresh)).unary_method("/jobs.Jobs/UpdateJob"))
+//!     .build()?;
+//! ```
+//!
+//! ## Resumable streams
+//!
+//! Server streams die when the network fails. `ResumableStream` reissues a server-streaming call with jittered backoff
+//! (using `options::RetryPolicy`) when the stream fails with a network error, a truncated response or a retryable status,
+//! and presents a single continuous stream of messages. A `resume` callback rewrites the request from the last received
+//! message, e.g. to set a resume cursor.
+//!
+//! ```rust,ignore
+//! use tonic_web_wasm_client::ResumableStream;
+//!
+//! let mut messages = ResumableStream::new(
+//!     EchoRequest { message: "John".to_string(), cursor: 0 },
+//!     move |request| {
+//!         let mut echo = echo.clone();
+//!         async move { echo.echo_infinite_stream(request).await }
+//!     },
+//!     |request, message| EchoRequest { cursor: message.cursor, ..request.clone() },
+//! );
+//! ```
 mod abort_guard;
+mod base_url;
 mod body_stream;
+mod builder;
 mod call;
 mod client;
+mod connect;
 mod content_type;
 mod error;
 mod fetch;
+pub mod layer;
+mod limit;
+mod metadata;
+#[cfg(test)]
+mod mock_fetch;
 pub mod options;
 mod response_body;
+mod resumable;
+mod retry;
+mod timer;
 
-pub use self::{client::Client, error::Error, response_body::ResponseBody};
+pub use self::{
+    body_stream::BodyStream,
+    builder::ClientBuilder,
+    client::Client,
+    error::{Error, JsErrorValue},
+    fetch::{FetchBackend, FetchBody, FetchFuture, JsFetch},
+    response_body::ResponseBody,
+    resumable::ResumableStream,
+};
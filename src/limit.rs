@@ -0,0 +1,149 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    future::poll_fn,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use crate::options::ConcurrencyLimit;
+
+/// Budgets of concurrent calls shared by all clones of a client
+#[derive(Debug)]
+pub struct Limiter {
+    unary: Arc<Semaphore>,
+    streaming: Arc<Semaphore>,
+    streaming_methods: HashSet<String>,
+}
+
+impl Limiter {
+    pub fn new(limit: ConcurrencyLimit) -> Self {
+        Self {
+            // A budget of zero calls would block all calls forever
+            unary: Semaphore::new(limit.max_unary.max(1)),
+            streaming: Semaphore::new(limit.max_streaming.max(1)),
+            streaming_methods: limit.streaming_methods,
+        }
+    }
+
+    /// Polls for a slot in the unary budget
+    pub fn poll_unary(&self, cx: &mut Context<'_>) -> Poll<Permit> {
+        Semaphore::poll_acquire(&self.unary, cx)
+    }
+
+    /// Waits for a slot in the unary budget
+    pub async fn unary(&self) -> Permit {
+        poll_fn(|cx| self.poll_unary(cx)).await
+    }
+
+    /// Waits for a slot in the streaming budget
+    pub async fn streaming(&self) -> Permit {
+        poll_fn(|cx| Semaphore::poll_acquire(&self.streaming, cx)).await
+    }
+
+    /// Returns `true` if `path` is a registered streaming method
+    pub fn is_streaming(&self, path: &str) -> bool {
+        self.streaming_methods.contains(path)
+    }
+}
+
+/// Counting semaphore which does not depend on an async runtime
+struct Semaphore {
+    state: Mutex<State>,
+}
+
+struct State {
+    available: usize,
+    waiters: Vec<Waker>,
+}
+
+impl Semaphore {
+    fn new(permits: usize) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(State {
+                available: permits,
+                waiters: Vec::new(),
+            }),
+        })
+    }
+
+    fn poll_acquire(this: &Arc<Self>, cx: &mut Context<'_>) -> Poll<Permit> {
+        let mut state = this.state.lock().unwrap_or_else(|error| error.into_inner());
+
+        if state.available > 0 {
+            state.available -= 1;
+            return Poll::Ready(Permit {
+                semaphore: this.clone(),
+            });
+        }
+
+        if !state
+            .waiters
+            .iter()
+            .any(|waker| waker.will_wake(cx.waker()))
+        {
+            state.waiters.push(cx.waker().clone());
+        }
+
+        Poll::Pending
+    }
+
+    fn release(&self) {
+        let waiters = {
+            let mut state = self.state.lock().unwrap_or_else(|error| error.into_inner());
+            state.available += 1;
+            std::mem::take(&mut state.waiters)
+        };
+
+        // All waiters are woken because a woken waiter may have been dropped without acquiring the permit.
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+}
+
+impl fmt::Debug for Semaphore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock().unwrap_or_else(|error| error.into_inner());
+
+        f.debug_struct("Semaphore")
+            .field("available", &state.available)
+            .field("waiters", &state.waiters.len())
+            .finish()
+    }
+}
+
+/// Slot in a budget of concurrent calls which is released when dropped
+pub struct Permit {
+    semaphore: Arc<Semaphore>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.semaphore.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::Waker;
+
+    use super::*;
+
+    #[test]
+    fn test_semaphore() {
+        let semaphore = Semaphore::new(2);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let first = Semaphore::poll_acquire(&semaphore, &mut cx);
+        let second = Semaphore::poll_acquire(&semaphore, &mut cx);
+        assert!(first.is_ready());
+        assert!(second.is_ready());
+        assert!(Semaphore::poll_acquire(&semaphore, &mut cx).is_pending());
+
+        drop(first);
+        let third = Semaphore::poll_acquire(&semaphore, &mut cx);
+        assert!(third.is_ready());
+        assert!(Semaphore::poll_acquire(&semaphore, &mut cx).is_pending());
+    }
+}
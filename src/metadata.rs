@@ -0,0 +1,76 @@
+use base64::Engine;
+use http::{HeaderMap, HeaderValue, header::HeaderName};
+
+use crate::content_type::BASE64;
+
+/// Suffix of metadata keys with binary values
+const BINARY_SUFFIX: &str = "-bin";
+
+/// Normalizes binary metadata (keys ending with `-bin`) so that `tonic` can decode it
+///
+/// Browsers combine repeated headers into a single comma separated value, and servers may use padded, unpadded or
+/// URL-safe base64. Each value of binary metadata is split into a separate entry and re-encoded as unpadded standard
+/// base64. Values which are not valid base64 are kept as they are.
+pub fn normalize_binary_metadata(headers: &mut HeaderMap) {
+    let binary_keys: Vec<HeaderName> = headers
+        .keys()
+        .filter(|key| key.as_str().ends_with(BINARY_SUFFIX))
+        .cloned()
+        .collect();
+
+    for key in binary_keys {
+        let values: Vec<HeaderValue> = headers
+            .get_all(&key)
+            .iter()
+            .flat_map(|value| value.as_bytes().split(|byte| *byte == b','))
+            .map(<[u8]>::trim_ascii)
+            .filter(|value| !value.is_empty())
+            .filter_map(|value| normalize_binary_value(value).ok())
+            .collect();
+
+        headers.remove(&key);
+
+        for value in values {
+            headers.append(key.clone(), value);
+        }
+    }
+}
+
+fn normalize_binary_value(value: &[u8]) -> Result<HeaderValue, http::header::InvalidHeaderValue> {
+    let standard: Vec<u8> = value
+        .iter()
+        .map(|byte| match byte {
+            b'-' => b'+',
+            b'_' => b'/',
+            byte => *byte,
+        })
+        .collect();
+
+    match BASE64.decode(standard) {
+        Ok(decoded) => HeaderValue::from_str(&BASE64.encode(decoded)),
+        Err(_) => HeaderValue::from_bytes(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_binary_metadata() {
+        let mut headers = HeaderMap::new();
+        headers.append("x-session-bin", HeaderValue::from_static("AAE=, AgM"));
+        headers.append("x-session-bin", HeaderValue::from_static("-_8"));
+        headers.append("x-invalid-bin", HeaderValue::from_static("*"));
+        headers.append("x-session-id", HeaderValue::from_static("a, b"));
+
+        normalize_binary_metadata(&mut headers);
+
+        assert_eq!(
+            headers.get_all("x-session-bin").iter().collect::<Vec<_>>(),
+            ["AAE", "AgM", "+/8"]
+        );
+        assert_eq!(headers["x-invalid-bin"], "*");
+        assert_eq!(headers["x-session-id"], "a, b");
+    }
+}
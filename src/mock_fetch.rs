@@ -0,0 +1,238 @@
+//! In-memory [`FetchBackend`] implementation for unit tests
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures_util::{StreamExt, stream};
+use http::{HeaderMap, HeaderValue, Request, Response, StatusCode, header::HeaderName};
+use http_body::{Body, Frame, SizeHint};
+use tonic::{
+    Status,
+    codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder},
+};
+use tower_service::Service;
+
+use crate::{
+    Client, Error, ResponseBody,
+    body_stream::BodyStream,
+    fetch::{FetchBackend, FetchBody, FetchFuture},
+    options::FetchOptions,
+};
+
+/// Request recorded by [`MockFetch`]
+pub struct RecordedRequest {
+    pub request: Request<Option<Bytes>>,
+    pub timeout: Option<Duration>,
+}
+
+/// [`FetchBackend`] implementation which answers with a canned response and records requests
+pub struct MockFetch {
+    status: StatusCode,
+    headers: HeaderMap,
+    chunks: RefCell<Option<Vec<Result<Bytes, Error>>>>,
+    idle: bool,
+    requests: RefCell<Vec<RecordedRequest>>,
+}
+
+impl MockFetch {
+    pub fn new() -> Self {
+        Self {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            chunks: RefCell::new(Some(Vec::new())),
+            idle: false,
+            requests: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Creates a mock answering with a `grpc-web` response
+    pub fn grpc_web() -> Self {
+        Self::new().header("content-type", "application/grpc-web+proto")
+    }
+
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = StatusCode::from_u16(status).unwrap();
+        self
+    }
+
+    pub fn header(mut self, name: &'static str, value: &'static str) -> Self {
+        self.headers.append(
+            HeaderName::from_static(name),
+            HeaderValue::from_static(value),
+        );
+        self
+    }
+
+    pub fn chunk(self, chunk: impl Into<Bytes>) -> Self {
+        self.chunks
+            .borrow_mut()
+            .get_or_insert_default()
+            .push(Ok(chunk.into()));
+        self
+    }
+
+    pub fn error(self, error: Error) -> Self {
+        self.chunks
+            .borrow_mut()
+            .get_or_insert_default()
+            .push(Err(error));
+        self
+    }
+
+    /// Keeps the response body open after all chunks are read
+    pub fn idle(mut self) -> Self {
+        self.idle = true;
+        self
+    }
+
+    /// Answers with a response without body
+    pub fn without_body(self) -> Self {
+        self.chunks.borrow_mut().take();
+        self
+    }
+
+    pub fn requests(&self) -> std::cell::Ref<'_, Vec<RecordedRequest>> {
+        self.requests.borrow()
+    }
+}
+
+impl FetchBackend for MockFetch {
+    fn fetch(
+        &self,
+        request: Request<FetchBody>,
+        _: &FetchOptions,
+        timeout: Option<Duration>,
+    ) -> FetchFuture {
+        let (parts, body) = request.into_parts();
+        let body = match body {
+            FetchBody::Full(bytes) => Some(bytes),
+            FetchBody::Empty | FetchBody::Streaming(_) => None,
+        };
+        self.requests.borrow_mut().push(RecordedRequest {
+            request: Request::from_parts(parts, body),
+            timeout,
+        });
+
+        let body_stream = self.chunks.borrow_mut().take().map(|chunks| {
+            if self.idle {
+                BodyStream::from_stream(stream::iter(chunks).chain(stream::pending()))
+            } else {
+                BodyStream::from_stream(stream::iter(chunks))
+            }
+        });
+
+        let mut response = Response::new(body_stream);
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+
+        Box::pin(async move { Ok(response) })
+    }
+}
+
+/// Encodes a `grpc-web` data frame
+pub fn data_frame(data: &[u8]) -> Bytes {
+    frame(0, data)
+}
+
+/// Encodes a `grpc-web` trailer frame
+pub fn trailer_frame(trailers: &str) -> Bytes {
+    frame(0x80, trailers.as_bytes())
+}
+
+fn frame(flag: u8, payload: &[u8]) -> Bytes {
+    let mut buf = BytesMut::with_capacity(payload.len() + 5);
+    buf.put_u8(flag);
+    buf.put_u32(payload.len() as u32);
+    buf.put_slice(payload);
+    buf.freeze()
+}
+
+/// Client whose response bodies are `Send`, as required by `tonic::client::Grpc`
+#[derive(Clone)]
+pub struct GrpcClient(pub Client);
+
+impl Service<Request<tonic::body::Body>> for GrpcClient {
+    type Response = Response<SendBody>;
+
+    type Error = Error;
+
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<tonic::body::Body>) -> Self::Future {
+        let response = self.0.call(request);
+        Box::pin(async move { Ok(response.await?.map(SendBody)) })
+    }
+}
+
+/// Response body which is `Send`
+pub struct SendBody(ResponseBody);
+
+// SAFETY: unit tests poll calls to completion on the thread which started them, so bodies never move between threads.
+unsafe impl Send for SendBody {}
+
+impl Body for SendBody {
+    type Data = Bytes;
+
+    type Error = Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Pin::new(&mut self.0).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.0.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.0.size_hint()
+    }
+}
+
+/// Codec passing messages through as raw bytes
+pub struct BytesCodec;
+
+impl Codec for BytesCodec {
+    type Encode = Bytes;
+    type Decode = Bytes;
+    type Encoder = BytesCodec;
+    type Decoder = BytesCodec;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        BytesCodec
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        BytesCodec
+    }
+}
+
+impl Encoder for BytesCodec {
+    type Item = Bytes;
+    type Error = Status;
+
+    fn encode(&mut self, item: Bytes, dst: &mut EncodeBuf<'_>) -> Result<(), Status> {
+        dst.put(item);
+        Ok(())
+    }
+}
+
+impl Decoder for BytesCodec {
+    type Item = Bytes;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Bytes>, Status> {
+        Ok(Some(src.copy_to_bytes(src.remaining())))
+    }
+}
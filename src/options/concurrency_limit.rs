@@ -0,0 +1,58 @@
+use std::collections::HashSet;
+
+/// Limits of concurrent calls made by a [`Client`](crate::Client)
+///
+/// Browsers open at most six HTTP/1.1 connections per origin, and calls exceeding this limit wait for a free connection
+/// without any feedback. Long-lived server streams each hold a connection, so a few of them can stall all other calls.
+///
+/// Unary calls and streaming calls have separate budgets, so streams can never take connections needed by unary
+/// calls. The unary budget is enforced in `poll_ready`, which applies backpressure to callers. Calls to streaming
+/// methods (which need to be registered, as the transport can not tell them apart) wait for a slot in the streaming
+/// budget after being called. A call holds its slot until its response body is dropped.
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimit {
+    /// Maximum number of concurrent unary calls
+    pub max_unary: usize,
+
+    /// Maximum number of concurrent streaming calls
+    pub max_streaming: usize,
+
+    /// Paths of streaming methods (e.g. `/echo.Echo/EchoStream`)
+    pub streaming_methods: HashSet<String>,
+}
+
+impl Default for ConcurrencyLimit {
+    fn default() -> Self {
+        // Fits into six connections per origin allowed by browsers over HTTP/1.1
+        Self {
+            max_unary: 4,
+            max_streaming: 2,
+            streaming_methods: HashSet::new(),
+        }
+    }
+}
+
+impl ConcurrencyLimit {
+    /// Create new `ConcurrencyLimit` with default values
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set maximum number of concurrent unary calls
+    pub fn max_unary(mut self, max_unary: usize) -> Self {
+        self.max_unary = max_unary;
+        self
+    }
+
+    /// Set maximum number of concurrent streaming calls
+    pub fn max_streaming(mut self, max_streaming: usize) -> Self {
+        self.max_streaming = max_streaming;
+        self
+    }
+
+    /// Register a streaming method (e.g. `/echo.Echo/EchoStream`)
+    pub fn streaming_method(mut self, path: impl Into<String>) -> Self {
+        self.streaming_methods.insert(path.into());
+        self
+    }
+}
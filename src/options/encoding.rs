@@ -0,0 +1,11 @@
+/// Request's `grpc-web` encoding
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Encoding {
+    /// Sends request body as raw binary (`application/grpc-web+proto`).
+    #[default]
+    Binary,
+
+    /// Sends request body as base64 encoded text (`application/grpc-web-text+proto`). This is useful when proxies
+    /// between the client and server do not preserve binary request bodies.
+    Text,
+}
@@ -1,35 +1,69 @@
 //! Options for underlying `fetch` call
 mod cache;
+mod concurrency_limit;
 mod credentials;
+mod encoding;
 mod mode;
+mod protocol;
 mod redirect;
 mod referrer_policy;
+mod retry;
+mod user_agent;
 
 use std::time::Duration;
 
+use http::HeaderMap;
+use tonic::codec::CompressionEncoding;
+
 use crate::abort_guard::AbortGuard;
 
 pub use self::{
-    cache::Cache, credentials::Credentials, mode::Mode, redirect::Redirect,
+    cache::Cache,
+    concurrency_limit::ConcurrencyLimit,
+    credentials::Credentials,
+    encoding::Encoding,
+    mode::Mode,
+    protocol::{ConnectCodec, ConnectOptions, Protocol},
+    redirect::Redirect,
     referrer_policy::ReferrerPolicy,
+    retry::RetryPolicy,
+    user_agent::UserAgent,
 };
 use web_sys::RequestInit;
 
 /// Options for underlying `fetch` call
 #[derive(Debug, Clone, Default)]
 pub struct FetchOptions {
+    /// Compression encodings accepted for responses
+    pub accept_compressed: Option<Vec<CompressionEncoding>>,
+
     /// Request's cache mode
     pub cache: Option<Cache>,
 
     /// Request's credentials mode
     pub credentials: Option<Credentials>,
 
+    /// Request's `grpc-web` encoding (Connect calls fail with `Code::Unimplemented` if set to [`Encoding::Text`])
+    pub encoding: Option<Encoding>,
+
+    /// Default headers sent with every request
+    pub headers: Option<HeaderMap>,
+
     /// Requests's integrity
     pub integrity: Option<String>,
 
+    /// Maximum size of a single response message
+    pub max_message_size: Option<usize>,
+
+    /// Maximum size of response trailers
+    pub max_trailer_size: Option<usize>,
+
     /// Request's mode
     pub mode: Option<Mode>,
 
+    /// Protocol used for calls
+    pub protocol: Option<Protocol>,
+
     /// Request's redirect mode
     pub redirect: Option<Redirect>,
 
@@ -39,8 +73,17 @@ pub struct FetchOptions {
     /// Request's referrer policy
     pub referrer_policy: Option<ReferrerPolicy>,
 
+    /// Whether request's body is streamed to the server
+    pub request_streaming: Option<bool>,
+
+    /// Request's retry policy
+    pub retry: Option<RetryPolicy>,
+
     /// Request's timeout duration
     pub timeout: Option<Duration>,
+
+    /// Value of `x-user-agent` header
+    pub user_agent: Option<UserAgent>,
 }
 
 impl FetchOptions {
@@ -49,6 +92,19 @@ impl FetchOptions {
         Default::default()
     }
 
+    /// Enable accepting responses compressed with `encoding`
+    ///
+    /// The encoding is advertised to the server in `grpc-accept-encoding` header unless the request already contains
+    /// it. Responses are decompressed by `tonic`, so the generated client must enable the same encodings using
+    /// `accept_compressed`. Compression encodings are available behind `gzip`, `deflate` and `zstd` features.
+    pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+        let encodings = self.accept_compressed.get_or_insert_default();
+        if !encodings.contains(&encoding) {
+            encodings.push(encoding);
+        }
+        self
+    }
+
     /// Set request's cache mode
     pub fn cache(mut self, cache: Cache) -> Self {
         self.cache = Some(cache);
@@ -61,18 +117,58 @@ impl FetchOptions {
         self
     }
 
+    /// Set request's `grpc-web` encoding
+    ///
+    /// Only applies to the `grpc-web` protocol. Calls using [`Protocol::Connect`] fail with `Code::Unimplemented` if
+    /// the encoding is [`Encoding::Text`].
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// Set default headers sent with every request (e.g. API keys or tenant IDs)
+    ///
+    /// Request metadata replaces default headers with the same name. Protocol headers (`content-type` and
+    /// `x-grpc-web`) are always set by the client and can not be overridden.
+    pub fn headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = Some(headers);
+        self
+    }
+
     /// Set request's integrity
     pub fn integrity(mut self, integrity: String) -> Self {
         self.integrity = Some(integrity);
         self
     }
 
+    /// Set maximum size of a single response message (defaults to 4 MiB)
+    ///
+    /// Responses containing a larger message fail with `Code::ResourceExhausted` before the message is buffered.
+    pub fn max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = Some(max_message_size);
+        self
+    }
+
+    /// Set maximum size of response trailers (defaults to 8 KiB)
+    ///
+    /// Responses with larger trailers fail with `Code::ResourceExhausted` before the trailers are buffered.
+    pub fn max_trailer_size(mut self, max_trailer_size: usize) -> Self {
+        self.max_trailer_size = Some(max_trailer_size);
+        self
+    }
+
     /// Set request's mode
     pub fn mode(mut self, mode: Mode) -> Self {
         self.mode = Some(mode);
         self
     }
 
+    /// Set protocol used for calls (defaults to `grpc-web`)
+    pub fn protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
     /// Set request's redirect mode
     pub fn redirect(mut self, redirect: Redirect) -> Self {
         self.redirect = Some(redirect);
@@ -91,13 +187,44 @@ impl FetchOptions {
         self
     }
 
+    /// Set whether request's body is streamed to the server
+    ///
+    /// By default, the whole request body is collected before the `fetch` call is made, which means that a
+    /// client-streaming call only starts after the request stream ends. When enabled, the request body is piped into a
+    /// `ReadableStream` as it is produced. This requires browser support for streaming uploads (currently
+    /// Chromium-based browsers) and a server reachable over HTTP/2 or newer. If the browser does not support streaming
+    /// uploads, calls fail with [`Error::RequestStreamingNotSupported`](crate::Error::RequestStreamingNotSupported).
+    pub fn request_streaming(mut self, request_streaming: bool) -> Self {
+        self.request_streaming = Some(request_streaming);
+        self
+    }
+
+    /// Set request's retry policy
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
     /// Set request's timeout duration
     pub fn timeout(mut self, timeout: Duration) -> Self {
         self.timeout = Some(timeout);
         self
     }
 
-    pub(crate) fn request_init(&self) -> Result<(RequestInit, AbortGuard), crate::Error> {
+    /// Set value of `x-user-agent` header identifying the client
+    ///
+    /// Browsers do not allow setting `User-Agent` header, so `grpc-web` clients identify themselves using
+    /// `x-user-agent` instead. Defaults to `grpc-web-rust/<version>`. Passing [`UserAgent::Suppressed`] suppresses the
+    /// header.
+    pub fn user_agent(mut self, user_agent: UserAgent) -> Self {
+        self.user_agent = Some(user_agent);
+        self
+    }
+
+    pub(crate) fn request_init(
+        &self,
+        timeout: Option<Duration>,
+    ) -> Result<(RequestInit, AbortGuard), crate::Error> {
         let init = RequestInit::new();
 
         if let Some(cache) = self.cache {
@@ -130,7 +257,7 @@ impl FetchOptions {
 
         let mut abort = AbortGuard::new()?;
 
-        if let Some(timeout) = self.timeout {
+        if let Some(timeout) = timeout {
             abort.timeout(timeout);
         }
 
@@ -0,0 +1,76 @@
+use std::collections::HashSet;
+
+/// Protocol used for calls
+#[derive(Debug, Clone, Default)]
+pub enum Protocol {
+    /// [`grpc-web`](https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-WEB.md) protocol
+    #[default]
+    GrpcWeb,
+
+    /// [Connect](https://connectrpc.com/docs/protocol) protocol
+    Connect(ConnectOptions),
+}
+
+/// Codec of messages sent using Connect protocol
+///
+/// Messages are encoded by the codec of the generated `tonic` client, so this only selects the content type which
+/// is sent to the server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConnectCodec {
+    /// Binary protobuf messages (`application/proto`)
+    #[default]
+    Proto,
+
+    /// JSON messages (`application/json`)
+    Json,
+}
+
+impl ConnectCodec {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            ConnectCodec::Proto => "proto",
+            ConnectCodec::Json => "json",
+        }
+    }
+}
+
+/// Options for Connect protocol
+///
+/// Connect uses different requests for unary and streaming calls, which can not be distinguished by the transport.
+/// Calls are sent as unary requests unless their method is registered as a streaming method.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectOptions {
+    /// Codec of messages
+    pub codec: ConnectCodec,
+
+    /// Paths of streaming methods (e.g. `/echo.Echo/EchoStream`)
+    pub streaming_methods: HashSet<String>,
+
+    /// Paths of side-effect free unary methods which are sent as `GET` requests
+    pub get_methods: HashSet<String>,
+}
+
+impl ConnectOptions {
+    /// Create new `ConnectOptions` with default values
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set codec of messages
+    pub fn codec(mut self, codec: ConnectCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Register a streaming method (e.g. `/echo.Echo/EchoStream`)
+    pub fn streaming_method(mut self, path: impl Into<String>) -> Self {
+        self.streaming_methods.insert(path.into());
+        self
+    }
+
+    /// Register a side-effect free unary method which is sent as a cacheable `GET` request
+    pub fn get_method(mut self, path: impl Into<String>) -> Self {
+        self.get_methods.insert(path.into());
+        self
+    }
+}
@@ -0,0 +1,144 @@
+use std::{collections::HashSet, time::Duration};
+
+use tonic::Code;
+
+/// Request's retry policy
+///
+/// Failed calls are retried with jittered exponential backoff as described in
+/// [gRPC Retry Design](https://github.com/grpc/proposal/blob/master/A6-client-retries.md). A call is only retried
+/// before any response message is handed over to the caller, which makes the policy safe to use with unary and
+/// server-streaming calls. Retries are disabled when request streaming is enabled because the request body cannot be
+/// replayed.
+///
+/// A failed call may still have been processed by the server, so only calls of methods registered with
+/// [`RetryPolicy::idempotent_method`] are retried. Calls of other methods (e.g. mutations) are sent once.
+/// [`ResumableStream`](crate::ResumableStream) reissues its stream regardless of registered methods.
+///
+/// Calls of registered unary methods (and calls of methods not registered as streaming in
+/// [`ConnectOptions`](crate::options::ConnectOptions)) resolve once the first response message or the final status is
+/// received, so failures reported in the response body are retried as well. Other calls (which may be streams, as the
+/// transport can not tell them apart) resolve as soon as response headers are received, and are only retried on network
+/// errors and trailers-only responses.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the original call
+    pub max_attempts: u32,
+
+    /// Backoff before the first retry
+    pub initial_backoff: Duration,
+
+    /// Upper bound of backoff between retries
+    pub max_backoff: Duration,
+
+    /// Multiplier applied to backoff after each retry
+    pub backoff_multiplier: f64,
+
+    /// Status codes which are retried (network errors are always retried)
+    pub retryable_codes: Vec<Code>,
+
+    /// Paths of idempotent methods whose calls are retried (e.g. `/echo.Echo/Echo`)
+    pub idempotent_methods: HashSet<String>,
+
+    /// Paths of unary methods (e.g. `/echo.Echo/Echo`)
+    pub unary_methods: HashSet<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            backoff_multiplier: 2.0,
+            retryable_codes: vec![Code::Unavailable],
+            idempotent_methods: HashSet::new(),
+            unary_methods: HashSet::new(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create new `RetryPolicy` with default values
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set maximum number of attempts, including the original call
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Set backoff before the first retry
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Set upper bound of backoff between retries
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Set multiplier applied to backoff after each retry
+    pub fn backoff_multiplier(mut self, backoff_multiplier: f64) -> Self {
+        self.backoff_multiplier = backoff_multiplier;
+        self
+    }
+
+    /// Set status codes which are retried
+    pub fn retryable_codes(mut self, retryable_codes: Vec<Code>) -> Self {
+        self.retryable_codes = retryable_codes;
+        self
+    }
+
+    /// Register an idempotent method (e.g. `/echo.Echo/Echo`) whose calls are retried
+    pub fn idempotent_method(mut self, path: impl Into<String>) -> Self {
+        self.idempotent_methods.insert(path.into());
+        self
+    }
+
+    /// Register a unary method (e.g. `/echo.Echo/Echo`)
+    pub fn unary_method(mut self, path: impl Into<String>) -> Self {
+        self.unary_methods.insert(path.into());
+        self
+    }
+
+    /// Returns upper bound of backoff before given retry (starting from `1`), without jitter
+    pub(crate) fn backoff(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let backoff = self.initial_backoff.as_secs_f64() * self.backoff_multiplier.powi(exponent);
+
+        if backoff.is_finite() && backoff < self.max_backoff.as_secs_f64() {
+            Duration::from_secs_f64(backoff.max(0.0))
+        } else {
+            self.max_backoff
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::new()
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_secs(1))
+            .backoff_multiplier(2.0);
+
+        let vals = [
+            (1, Duration::from_millis(100)),
+            (2, Duration::from_millis(200)),
+            (3, Duration::from_millis(400)),
+            (4, Duration::from_millis(800)),
+            (5, Duration::from_secs(1)),
+            (u32::MAX, Duration::from_secs(1)),
+        ];
+        for (retry, expected) in vals.iter() {
+            assert_eq!(policy.backoff(*retry), *expected);
+        }
+    }
+}
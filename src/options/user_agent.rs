@@ -0,0 +1,13 @@
+/// Value of `x-user-agent` header identifying the client
+#[derive(Debug, Clone, Default)]
+pub enum UserAgent {
+    /// Sends `grpc-web-rust/<version>`.
+    #[default]
+    Default,
+
+    /// Sends given value.
+    Custom(String),
+
+    /// Does not send the header.
+    Suppressed,
+}
@@ -4,21 +4,75 @@ use std::{
     task::{Context, Poll, ready},
 };
 
-use base64::{Engine, prelude::BASE64_STANDARD};
+use base64::{DecodeSliceError, Engine};
 use byteorder::{BigEndian, ByteOrder};
 use bytes::{BufMut, Bytes, BytesMut};
 use http::{HeaderMap, HeaderValue, header::HeaderName};
 use http_body::Body;
 use httparse::{EMPTY_HEADER, Status};
 use pin_project::pin_project;
-use wasm_bindgen::JsCast;
-use web_sys::ReadableStream;
-
-use crate::{Error, abort_guard::AbortGuard, body_stream::BodyStream, content_type::Encoding};
+use tonic::Status as GrpcStatus;
+
+use crate::{
+    Error,
+    body_stream::{BodyStream, Chunk},
+    connect::{decompress_end_stream, parse_end_stream},
+    content_type::{BASE64, Encoding},
+    limit::Permit,
+    metadata::normalize_binary_metadata,
+};
 
 /// If 8th MSB of a frame is `0` for data and `1` for trailer
 const TRAILER_BIT: u8 = 0b10000000;
 
+/// If LSB of a data frame is `1`, the message is compressed using the encoding in `grpc-encoding` header
+const COMPRESSED_BIT: u8 = 0b00000001;
+
+/// If 2nd LSB of a frame is `1`, the frame is Connect's end-of-stream message
+const END_STREAM_BIT: u8 = 0b00000010;
+
+/// Default maximum size of a single message (same as `tonic`'s default decoding limit)
+pub(crate) const DEFAULT_MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Default maximum size of trailers (same as gRPC's default maximum metadata size)
+pub(crate) const DEFAULT_MAX_TRAILER_SIZE: usize = 8 * 1024;
+
+fn decode_base64(input: &[u8], buf: &mut BytesMut) -> Result<(), Error> {
+    if input.is_empty() {
+        return Ok(());
+    }
+
+    // Decode directly into the buffer
+    let len = buf.len();
+    buf.resize(len + base64::decoded_len_estimate(input.len()), 0);
+
+    match BASE64.decode_slice(input, &mut buf[len..]) {
+        Ok(decoded_len) => {
+            buf.truncate(len + decoded_len);
+            Ok(())
+        }
+        Err(DecodeSliceError::DecodeError(e)) => Err(e.into()),
+        Err(DecodeSliceError::OutputSliceTooSmall) => Err(Error::MalformedResponse),
+    }
+}
+
+/// Removes whitespace and line breaks, and converts URL-safe alphabet to standard alphabet in `buf[start..]`
+fn normalize_base64(buf: &mut BytesMut, start: usize) {
+    let mut len = start;
+
+    for index in start..buf.len() {
+        buf[len] = match buf[index] {
+            b' ' | b'\t' | b'\r' | b'\n' => continue,
+            b'-' => b'+',
+            b'_' => b'/',
+            byte => byte,
+        };
+        len += 1;
+    }
+
+    buf.truncate(len);
+}
+
 pub struct EncodedBytes {
     encoding: Encoding,
     raw_buf: BytesMut,
@@ -44,21 +98,34 @@ impl EncodedBytes {
     fn decode_base64_chunk(&mut self) -> Result<(), Error> {
         let index = self.max_decodable();
 
-        if self.raw_buf.len() >= index {
-            let decoded = BASE64_STANDARD
-                .decode(self.buf.split_to(index))
-                .map(Bytes::from)?;
-            self.buf.put(decoded);
+        if index == 0 {
+            return Ok(());
         }
 
-        Ok(())
+        let quanta = self.raw_buf.split_to(index);
+
+        // Servers may encode each frame separately, so padding can appear in the middle of the stream. Padding
+        // always ends a 4 byte quantum, so the input is decoded in segments which end with a padded quantum.
+        let mut start = 0;
+
+        for end in (4..=quanta.len()).step_by(4) {
+            if quanta[end - 1] == b'=' {
+                decode_base64(&quanta[start..end], &mut self.buf)?;
+                start = end;
+            }
+        }
+
+        decode_base64(&quanta[start..], &mut self.buf)
     }
 
-    fn append(&mut self, bytes: Bytes) -> Result<(), Error> {
+    fn append(&mut self, chunk: Chunk) -> Result<(), Error> {
         match self.encoding {
-            Encoding::None => self.buf.put(bytes),
+            Encoding::None => chunk.copy_into(&mut self.buf),
             Encoding::Base64 => {
-                self.raw_buf.put(bytes);
+                let start = self.raw_buf.len();
+                chunk.copy_into(&mut self.raw_buf);
+                normalize_base64(&mut self.raw_buf, start);
+
                 self.decode_base64_chunk()?;
             }
         }
@@ -66,6 +133,16 @@ impl EncodedBytes {
         Ok(())
     }
 
+    /// Decodes remaining bytes at the end of the stream (which may be unpadded)
+    fn finish(&mut self) -> Result<(), Error> {
+        if !self.raw_buf.is_empty() {
+            let remaining = self.raw_buf.split();
+            decode_base64(&remaining, &mut self.buf)?;
+        }
+
+        Ok(())
+    }
+
     fn take(&mut self, length: usize) -> BytesMut {
         let new_buf = self.buf.split_off(length);
         std::mem::replace(&mut self.buf, new_buf)
@@ -96,12 +173,52 @@ pub enum ReadState {
     Done,
 }
 
-impl ReadState {
-    fn finished_data(&self) -> bool {
-        matches!(self, ReadState::TrailerLength)
-            || matches!(self, ReadState::Trailer(_))
-            || matches!(self, ReadState::Done)
+/// Parses trailers sent in a trailer frame
+///
+/// Trailers may be terminated by `\r\n` or `\n` (sent by some proxies), the final line terminator is optional and
+/// repeated keys are preserved.
+fn parse_trailers(trailer_bytes: &[u8]) -> Result<HeaderMap, Error> {
+    let trailer_bytes = trailer_bytes.trim_ascii_end();
+
+    let mut headers_bytes = BytesMut::with_capacity(trailer_bytes.len() + 4);
+    headers_bytes.put_slice(trailer_bytes);
+    if !trailer_bytes.is_empty() {
+        headers_bytes.put_slice(b"\r\n");
+    }
+    headers_bytes.put_slice(b"\r\n");
+
+    // Each trailer takes at least one line
+    let max_trailers = headers_bytes.iter().filter(|byte| **byte == b'\n').count();
+    let mut trailers_buf = vec![EMPTY_HEADER; max_trailers];
+
+    let parsed_trailers = match httparse::parse_headers(&headers_bytes, &mut trailers_buf)
+        .map_err(|_| Error::HeaderParsingError)?
+    {
+        Status::Complete((_, headers)) => Ok(headers),
+        Status::Partial => Err(Error::HeaderParsingError),
+    }?;
+
+    let mut trailers = HeaderMap::with_capacity(parsed_trailers.len());
+
+    for parsed_trailer in parsed_trailers {
+        let header_name = HeaderName::from_bytes(parsed_trailer.name.as_bytes())?;
+        let header_value = HeaderValue::from_bytes(parsed_trailer.value)?;
+        trailers.append(header_name, header_value);
+    }
+
+    Ok(trailers)
+}
+
+/// Fails with `Code::ResourceExhausted` if a frame is longer than `max_length`
+fn check_length(frame: &str, length: u32, max_length: usize) -> Result<(), Error> {
+    if length as usize > max_length {
+        return Err(GrpcStatus::resource_exhausted(format!(
+            "{frame} length too large: found {length} bytes, the limit is {max_length} bytes"
+        ))
+        .into());
     }
+
+    Ok(())
 }
 
 /// Type to handle HTTP response
@@ -115,28 +232,105 @@ pub struct ResponseBody {
     trailer: Option<HeaderMap>,
     state: ReadState,
     finished_stream: bool,
+    max_message_size: usize,
+    max_trailer_size: usize,
+    compression: bool,
+    connect: bool,
+    connect_encoding: Option<HeaderValue>,
+    compressed_trailer: bool,
+    permit: Option<Permit>,
 }
 
 impl ResponseBody {
-    pub(crate) fn new(
-        body_stream: ReadableStream,
-        content_type: &str,
-        abort: AbortGuard,
-    ) -> Result<Self, Error> {
-        let body_stream =
-            wasm_streams::ReadableStream::from_raw(body_stream.unchecked_into()).into_stream();
-
+    pub(crate) fn new(body_stream: BodyStream, content_type: &str) -> Result<Self, Error> {
         Ok(Self {
-            body_stream: BodyStream::new(body_stream, abort),
+            body_stream,
             buf: EncodedBytes::new(content_type)?,
             incomplete_data: BytesMut::new(),
             data: None,
             trailer: None,
             state: ReadState::CompressionFlag,
             finished_stream: false,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            max_trailer_size: DEFAULT_MAX_TRAILER_SIZE,
+            compression: false,
+            connect: false,
+            connect_encoding: None,
+            compressed_trailer: false,
+            permit: None,
         })
     }
 
+    /// Sets maximum sizes of messages and trailers (a larger frame fails with `Code::ResourceExhausted`)
+    pub(crate) fn with_limits(mut self, max_message_size: usize, max_trailer_size: usize) -> Self {
+        self.max_message_size = max_message_size;
+        self.max_trailer_size = max_trailer_size;
+        self
+    }
+
+    /// Sets whether a compression encoding was negotiated (compressed frames are rejected otherwise)
+    pub(crate) fn with_compression(mut self, compression: bool) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Reads the body as a Connect streaming response, which ends with an end-of-stream message instead of trailers
+    ///
+    /// `encoding` is the compression encoding of the response, which is used to decompress a compressed end-of-stream
+    /// message.
+    pub(crate) fn with_connect(mut self, encoding: Option<HeaderValue>) -> Self {
+        self.connect = true;
+        self.connect_encoding = encoding;
+        self
+    }
+
+    /// Holds a slot in a budget of concurrent calls until the body is dropped
+    pub(crate) fn with_permit(mut self, permit: Option<Permit>) -> Self {
+        self.permit = permit;
+        self
+    }
+
+    /// Creates a response body which contains a single encoded message frame followed by given trailers
+    pub(crate) fn from_message(frame: BytesMut, trailers: HeaderMap) -> Self {
+        Self {
+            data: Some(frame),
+            trailer: Some(trailers),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a response body which only contains given trailers
+    pub(crate) fn from_trailers(trailers: HeaderMap) -> Self {
+        Self {
+            trailer: Some(trailers),
+            ..Default::default()
+        }
+    }
+
+    /// Reads response body until either a data frame or trailers are available, without handing them over to the
+    /// caller
+    pub(crate) fn poll_prefetch(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Error>> {
+        loop {
+            if self.data.is_some() || self.state == ReadState::Done || self.finished_stream {
+                return Poll::Ready(Ok(()));
+            }
+
+            ready!(self.as_mut().read_stream(cx))?;
+            self.as_mut().step()?;
+        }
+    }
+
+    /// Returns trailers if the response did not contain any data before them
+    pub(crate) fn trailers_only(&self) -> Option<&HeaderMap> {
+        match self.data {
+            None => self.trailer.as_ref(),
+            Some(_) => None,
+        }
+    }
+
     fn read_stream(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
         if self.finished_stream {
             return Poll::Ready(Ok(()));
@@ -144,20 +338,12 @@ impl ResponseBody {
 
         let this = self.project();
 
-        match ready!(this.body_stream.poll_frame(cx)) {
-            Some(Ok(frame)) => {
-                if let Some(data) = frame.data_ref()
-                    && let Err(e) = this.buf.append(data.clone())
-                {
-                    return Poll::Ready(Err(e));
-                };
-
-                Poll::Ready(Ok(()))
-            }
+        match ready!(this.body_stream.poll_chunk(cx)) {
+            Some(Ok(chunk)) => Poll::Ready(this.buf.append(chunk)),
             Some(Err(e)) => Poll::Ready(Err(e)),
             None => {
                 *this.finished_stream = true;
-                Poll::Ready(Ok(()))
+                Poll::Ready(this.buf.finish())
             }
         }
     }
@@ -174,11 +360,41 @@ impl ResponseBody {
                     } else {
                         let compression_flag = this.buf.take(1);
 
-                        if compression_flag[0] & TRAILER_BIT == 0 {
-                            this.incomplete_data.unsplit(compression_flag);
-                            *this.state = ReadState::DataLength;
+                        let trailer_flag = if *this.connect {
+                            END_STREAM_BIT
                         } else {
-                            *this.state = ReadState::TrailerLength;
+                            TRAILER_BIT
+                        };
+
+                        match compression_flag[0] {
+                            COMPRESSED_BIT if !*this.compression => {
+                                return Err(GrpcStatus::internal(
+                                    "received compressed message without grpc-encoding",
+                                )
+                                .into());
+                            }
+                            0 | COMPRESSED_BIT => {
+                                this.incomplete_data.unsplit(compression_flag);
+                                *this.state = ReadState::DataLength;
+                            }
+                            flag if flag == trailer_flag => *this.state = ReadState::TrailerLength,
+                            flag if *this.connect && flag == END_STREAM_BIT | COMPRESSED_BIT => {
+                                if !*this.compression {
+                                    return Err(GrpcStatus::internal(
+                                        "received compressed end-of-stream message without connect-content-encoding",
+                                    )
+                                    .into());
+                                }
+
+                                *this.compressed_trailer = true;
+                                *this.state = ReadState::TrailerLength;
+                            }
+                            flag => {
+                                return Err(GrpcStatus::internal(format!(
+                                    "received frame with reserved flags: {flag:#010b}"
+                                ))
+                                .into());
+                            }
                         }
                     }
                 }
@@ -190,6 +406,8 @@ impl ResponseBody {
                         let data_length_bytes = this.buf.take(4);
                         let data_length = BigEndian::read_u32(data_length_bytes.as_ref());
 
+                        check_length("message", data_length, *this.max_message_size)?;
+
                         this.incomplete_data.unsplit(data_length_bytes);
                         *this.state = ReadState::Data(data_length);
                     }
@@ -221,6 +439,8 @@ impl ResponseBody {
                     } else {
                         let trailer_length_bytes = this.buf.take(4);
                         let trailer_length = BigEndian::read_u32(trailer_length_bytes.as_ref());
+
+                        check_length("trailers", trailer_length, *this.max_trailer_size)?;
                         *this.state = ReadState::Trailer(trailer_length);
                     }
                 }
@@ -231,27 +451,31 @@ impl ResponseBody {
                         // Can't read trailer right now
                         return Ok(());
                     } else {
-                        let mut trailer_bytes = this.buf.take(trailer_length);
-                        trailer_bytes.put_u8(b'\n');
-
-                        let mut trailers_buf = [EMPTY_HEADER; 64];
-                        let parsed_trailers =
-                            match httparse::parse_headers(&trailer_bytes, &mut trailers_buf)
-                                .map_err(|_| Error::HeaderParsingError)?
-                            {
-                                Status::Complete((_, headers)) => Ok(headers),
-                                Status::Partial => Err(Error::HeaderParsingError),
-                            }?;
-
-                        let mut trailers = HeaderMap::with_capacity(parsed_trailers.len());
-
-                        for parsed_trailer in parsed_trailers {
-                            let header_name =
-                                HeaderName::from_bytes(parsed_trailer.name.as_bytes())?;
-                            let header_value = HeaderValue::from_bytes(parsed_trailer.value)?;
-                            trailers.insert(header_name, header_value);
-                        }
+                        let trailer_bytes = this.buf.take(trailer_length);
+
+                        let mut trailers = if *this.compressed_trailer {
+                            // Limit is exceeded by one byte to detect decompressed messages which are too large
+                            let end_stream = decompress_end_stream(
+                                this.connect_encoding.as_ref(),
+                                &trailer_bytes,
+                                *this.max_trailer_size + 1,
+                            )?;
+                            if end_stream.len() > *this.max_trailer_size {
+                                return Err(GrpcStatus::resource_exhausted(format!(
+                                    "decompressed trailers length too large: the limit is {} bytes",
+                                    this.max_trailer_size
+                                ))
+                                .into());
+                            }
+
+                            parse_end_stream(&end_stream)?
+                        } else if *this.connect {
+                            parse_end_stream(&trailer_bytes)?
+                        } else {
+                            parse_trailers(&trailer_bytes)?
+                        };
 
+                        normalize_binary_metadata(&mut trailers);
                         *this.trailer = Some(trailers);
 
                         *this.state = ReadState::Done;
@@ -280,7 +504,7 @@ impl Body for ResponseBody {
         }
 
         // If reading data is finished, return trailers (if available) before ending
-        if self.state.finished_data() {
+        if self.state == ReadState::Done {
             if let Some(trailers) = self.trailer.take() {
                 return Poll::Ready(Some(Ok(http_body::Frame::trailers(trailers))));
             }
@@ -302,8 +526,8 @@ impl Body for ResponseBody {
                 // If data is available in buffer, return that
                 let data = self.data.take().unwrap();
                 return Poll::Ready(Some(Ok(http_body::Frame::data(data.freeze()))));
-            } else if self.state.finished_data() {
-                // If we finished reading data, return trailers before ending
+            } else if self.state == ReadState::Done {
+                // If we finished reading trailers, return them before ending
                 if let Some(trailers) = self.trailer.take() {
                     return Poll::Ready(Some(Ok(http_body::Frame::trailers(trailers))));
                 }
@@ -330,6 +554,276 @@ impl Default for ResponseBody {
             trailer: None,
             state: ReadState::Done,
             finished_stream: true,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            max_trailer_size: DEFAULT_MAX_TRAILER_SIZE,
+            compression: false,
+            connect: false,
+            connect_encoding: None,
+            compressed_trailer: false,
+            permit: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::prelude::{BASE64_STANDARD, BASE64_URL_SAFE_NO_PAD};
+    use futures_util::{FutureExt, stream};
+    use http_body_util::BodyExt;
+
+    use tonic::Code;
+
+    use super::*;
+    use crate::mock_fetch::{data_frame, trailer_frame};
+
+    const TRAILERS: &str = "grpc-status: 0\r\ngrpc-message: ok\r\n";
+
+    fn response_body(chunks: Vec<Result<Bytes, Error>>, content_type: &str) -> ResponseBody {
+        ResponseBody::new(BodyStream::from_stream(stream::iter(chunks)), content_type).unwrap()
+    }
+
+    fn response(messages: &[&[u8]], trailers: &str) -> Bytes {
+        let mut bytes = BytesMut::new();
+        for message in messages {
+            bytes.put(data_frame(message));
+        }
+        bytes.put(trailer_frame(trailers));
+        bytes.freeze()
+    }
+
+    /// Collects data and trailers of response body
+    fn collect(body: ResponseBody) -> Result<(Bytes, Option<HeaderMap>), Error> {
+        let collected = body.collect().now_or_never().expect("ready body")?;
+        let trailers = collected.trailers().cloned();
+        Ok((collected.to_bytes(), trailers))
+    }
+
+    #[test]
+    fn test_single_chunk() {
+        let bytes = response(&[b"hello", b"world"], TRAILERS);
+        let body = response_body(vec![Ok(bytes)], "application/grpc-web+proto");
+
+        let (data, trailers) = collect(body).unwrap();
+        let trailers = trailers.expect("trailers");
+
+        let mut expected = BytesMut::new();
+        expected.put(data_frame(b"hello"));
+        expected.put(data_frame(b"world"));
+
+        assert_eq!(data, expected.freeze());
+        assert_eq!(trailers["grpc-status"], "0");
+        assert_eq!(trailers["grpc-message"], "ok");
+    }
+
+    #[test]
+    fn test_chunk_boundaries() {
+        let bytes = response(&[b"hello", b"", b"world"], TRAILERS);
+
+        for chunk_size in 1..bytes.len() {
+            let chunks = bytes
+                .chunks(chunk_size)
+                .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+                .collect();
+            let body = response_body(chunks, "application/grpc-web+proto");
+
+            let (data, trailers) = collect(body).unwrap();
+
+            assert_eq!(data.len(), 3 * 5 + 10, "chunk size {chunk_size}");
+            assert_eq!(
+                trailers.expect("trailers")["grpc-status"],
+                "0",
+                "chunk size {chunk_size}"
+            );
         }
     }
+
+    /// Splits `bytes` into chunks at given indices
+    fn split(bytes: &Bytes, indices: &[usize]) -> Vec<Result<Bytes, Error>> {
+        let mut chunks = Vec::new();
+        let mut start = 0;
+
+        for &index in indices {
+            chunks.push(Ok(bytes.slice(start..index)));
+            start = index;
+        }
+        chunks.push(Ok(bytes.slice(start..)));
+
+        chunks
+    }
+
+    /// Asserts that `encoded` decodes to `expected` data frames and trailers regardless of how it is split into
+    /// chunks (tries all ways of splitting into up to three chunks)
+    fn assert_base64_splits(encoded: &Bytes, expected: &[&[u8]]) {
+        let mut expected_data = BytesMut::new();
+        for message in expected {
+            expected_data.put(data_frame(message));
+        }
+
+        for i in 0..=encoded.len() {
+            for j in i..=encoded.len() {
+                let body = response_body(split(encoded, &[i, j]), "application/grpc-web-text");
+
+                let (data, trailers) =
+                    collect(body).unwrap_or_else(|e| panic!("split at {i} and {j}: {e}"));
+
+                assert_eq!(data, expected_data, "split at {i} and {j}");
+                assert_eq!(
+                    trailers.expect("trailers")["grpc-status"],
+                    "0",
+                    "split at {i} and {j}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_base64() {
+        let encoded = BASE64_STANDARD.encode(response(&[b"hello", b"world!"], TRAILERS));
+
+        assert_base64_splits(&encoded.into(), &[b"hello", b"world!"]);
+    }
+
+    #[test]
+    fn test_base64_padding_per_frame() {
+        // Each frame is encoded separately which results in padding in the middle of the stream
+        let encoded = [
+            BASE64_STANDARD.encode(data_frame(b"hello")),
+            BASE64_STANDARD.encode(data_frame(b"world!")),
+            BASE64_STANDARD.encode(trailer_frame(TRAILERS)),
+        ]
+        .concat();
+
+        assert!(encoded.trim_end_matches('=').contains('='));
+        assert_base64_splits(&encoded.into(), &[b"hello", b"world!"]);
+    }
+
+    #[test]
+    fn test_base64_whitespace() {
+        let encoded = BASE64_STANDARD.encode(response(&[b"hello"], TRAILERS));
+        let encoded = encoded
+            .as_bytes()
+            .chunks(7)
+            .map(|line| String::from_utf8_lossy(line).into_owned())
+            .collect::<Vec<_>>()
+            .join("\r\n ");
+
+        assert_base64_splits(&encoded.into(), &[b"hello"]);
+    }
+
+    #[test]
+    fn test_base64_url_safe() {
+        // Message chosen so that its encoding contains characters specific to URL-safe alphabet
+        let message: &[u8] = &[0xfb, 0xff, 0xbf];
+        let encoded = BASE64_URL_SAFE_NO_PAD.encode(response(&[message], TRAILERS));
+
+        assert!(encoded.contains('-') || encoded.contains('_'));
+        assert_base64_splits(&encoded.into(), &[message]);
+    }
+
+    #[test]
+    fn test_base64_invalid() {
+        let body = response_body(vec![Ok("AAAA*AAA".into())], "application/grpc-web-text");
+
+        assert!(matches!(collect(body), Err(Error::Base64DecodeError(_))));
+    }
+
+    #[test]
+    fn test_parse_trailers() {
+        let trailers = parse_trailers(b"grpc-status: 0\r\nx-audit: a\r\nx-audit: b\r\n").unwrap();
+        assert_eq!(trailers["grpc-status"], "0");
+        assert_eq!(
+            trailers.get_all("x-audit").iter().collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+
+        // Lenient line endings
+        let trailers = parse_trailers(b"grpc-status: 0\nGrpc-Message: ok\n").unwrap();
+        assert_eq!(trailers["grpc-status"], "0");
+        assert_eq!(trailers["grpc-message"], "ok");
+
+        let trailers = parse_trailers(b"grpc-status: 0").unwrap();
+        assert_eq!(trailers["grpc-status"], "0");
+
+        assert!(parse_trailers(b"").unwrap().is_empty());
+
+        // Large number of trailers
+        let trailer_bytes = (0..100)
+            .map(|i| format!("x-trailer-{i}: {i}\r\n"))
+            .collect::<String>();
+        assert_eq!(parse_trailers(trailer_bytes.as_bytes()).unwrap().len(), 100);
+
+        assert!(matches!(
+            parse_trailers(b"grpc-status 0\r\n"),
+            Err(Error::HeaderParsingError)
+        ));
+    }
+
+    #[test]
+    fn test_truncated_response() {
+        let bytes = response(&[b"hello"], TRAILERS);
+        let body = response_body(vec![Ok(bytes.slice(..8))], "application/grpc-web+proto");
+
+        assert!(matches!(collect(body), Err(Error::MalformedResponse)));
+    }
+
+    #[test]
+    fn test_stream_error() {
+        let bytes = response(&[b"hello"], TRAILERS);
+        let body = response_body(
+            vec![Ok(bytes.slice(..8)), Err(Error::MalformedResponse)],
+            "application/grpc-web+proto",
+        );
+
+        assert!(matches!(collect(body), Err(Error::MalformedResponse)));
+    }
+
+    #[test]
+    fn test_frame_validation() {
+        let frame = |flag: u8, payload: &[u8]| {
+            let mut bytes = BytesMut::new();
+            bytes.put_u8(flag);
+            bytes.put_u32(payload.len() as u32);
+            bytes.put_slice(payload);
+            bytes.put(trailer_frame(TRAILERS));
+            bytes.freeze()
+        };
+
+        let vals = [
+            (frame(0, b"hello"), false, None),
+            (frame(1, b"hello"), true, None),
+            (frame(1, b"hello"), false, Some(Code::Internal)),
+            (frame(0b00000010, b"hello"), true, Some(Code::Internal)),
+            (frame(0b10000001, b"hello"), true, Some(Code::Internal)),
+            (frame(0, &[0; 17]), false, Some(Code::ResourceExhausted)),
+            (
+                response(&[b"hello"], &"x".repeat(100)),
+                false,
+                Some(Code::ResourceExhausted),
+            ),
+        ];
+
+        for (bytes, compression, expected) in vals {
+            let body = response_body(vec![Ok(bytes)], "application/grpc-web+proto")
+                .with_limits(16, 64)
+                .with_compression(compression);
+
+            match (collect(body), expected) {
+                (Ok(_), None) => {}
+                (Err(Error::TonicStatusError(status)), Some(code)) => {
+                    assert_eq!(status.code(), code)
+                }
+                (result, expected) => panic!(
+                    "unexpected result {:?} (expected {expected:?})",
+                    result.err()
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_content_type() {
+        let body = ResponseBody::new(BodyStream::empty(), "text/html");
+
+        assert!(matches!(body, Err(Error::InvalidContentType(_))));
+    }
 }
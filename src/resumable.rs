@@ -0,0 +1,298 @@
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_util::{Stream, stream};
+use tonic::{Response, Status, Streaming};
+
+use crate::{error::is_transport_failure, options::RetryPolicy, retry::jitter, timer::sleep};
+
+type MessageStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>>>>;
+
+/// Server-streaming call which is transparently reissued when the transport fails
+///
+/// The call is reissued with jittered exponential backoff (using the given [`RetryPolicy`]) when the stream fails with
+/// a network error, a truncated response or a status listed in [`RetryPolicy::retryable_codes`]. Before each message
+/// is handed over to the caller, `resume` is called with the current request and the message to get the request used
+/// when the call is reissued (e.g. to set a resume cursor). The call is given up after
+/// [`RetryPolicy::max_attempts`] consecutive attempts fail without receiving a message, in which case the stream
+/// yields the last error and ends.
+///
+/// ```rust,ignore
+/// use tonic_web_wasm_client::ResumableStream;
+///
+/// let mut messages = ResumableStream::new(
+///     EchoRequest { message: "John".to_string(), cursor: 0 },
+///     move |request| {
+///         let mut echo = echo.clone();
+///         async move { echo.echo_infinite_stream(request).await }
+///     },
+///     |request, message| EchoRequest { cursor: message.cursor, ..request.clone() },
+/// );
+///
+/// while let Some(message) = messages.next().await {
+///     // ...
+/// }
+/// ```
+pub struct ResumableStream<T> {
+    inner: MessageStream<T>,
+}
+
+impl<T: 'static> ResumableStream<T> {
+    /// Creates a new resumable stream using the default [`RetryPolicy`]
+    pub fn new<M, F, Fut, R>(request: M, call: F, resume: R) -> Self
+    where
+        M: Clone + 'static,
+        F: FnMut(M) -> Fut + 'static,
+        Fut: Future<Output = Result<Response<Streaming<T>>, Status>> + 'static,
+        R: FnMut(&M, &T) -> M + 'static,
+    {
+        Self::new_with_policy(RetryPolicy::default(), request, call, resume)
+    }
+
+    /// Creates a new resumable stream with given [`RetryPolicy`]
+    pub fn new_with_policy<M, F, Fut, R>(
+        policy: RetryPolicy,
+        request: M,
+        call: F,
+        resume: R,
+    ) -> Self
+    where
+        M: Clone + 'static,
+        F: FnMut(M) -> Fut + 'static,
+        Fut: Future<Output = Result<Response<Streaming<T>>, Status>> + 'static,
+        R: FnMut(&M, &T) -> M + 'static,
+    {
+        let state = State {
+            policy,
+            request,
+            call,
+            resume,
+            stream: None,
+            failures: 0,
+            done: false,
+        };
+
+        Self {
+            inner: Box::pin(stream::unfold(state, State::next)),
+        }
+    }
+}
+
+impl<T> Stream for ResumableStream<T> {
+    type Item = Result<T, Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.as_mut().poll_next(cx)
+    }
+}
+
+impl<T> fmt::Debug for ResumableStream<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResumableStream").finish_non_exhaustive()
+    }
+}
+
+struct State<M, F, R, T> {
+    policy: RetryPolicy,
+    request: M,
+    call: F,
+    resume: R,
+    stream: Option<Streaming<T>>,
+    /// Number of consecutive attempts which failed without receiving a message
+    failures: u32,
+    done: bool,
+}
+
+impl<M, F, Fut, R, T> State<M, F, R, T>
+where
+    M: Clone,
+    F: FnMut(M) -> Fut,
+    Fut: Future<Output = Result<Response<Streaming<T>>, Status>>,
+    R: FnMut(&M, &T) -> M,
+{
+    async fn next(mut self) -> Option<(Result<T, Status>, Self)> {
+        if self.done {
+            return None;
+        }
+
+        loop {
+            let result = match self.stream {
+                Some(ref mut stream) => stream.message().await,
+                None => match (self.call)(self.request.clone()).await {
+                    Ok(response) => {
+                        self.stream = Some(response.into_inner());
+                        continue;
+                    }
+                    Err(status) => Err(status),
+                },
+            };
+
+            match result {
+                Ok(Some(message)) => {
+                    self.failures = 0;
+                    self.request = (self.resume)(&self.request, &message);
+                    return Some((Ok(message), self));
+                }
+                Ok(None) => return None,
+                Err(status) => {
+                    self.stream = None;
+                    self.failures += 1;
+
+                    if !is_resumable(&self.policy, &status)
+                        || self.failures >= self.policy.max_attempts
+                    {
+                        self.done = true;
+                        return Some((Err(status), self));
+                    }
+
+                    let backoff = self.policy.backoff(self.failures);
+                    if !backoff.is_zero() {
+                        sleep(jitter(backoff)).await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns `true` if the call failed because of the transport or with a retryable status
+fn is_resumable(policy: &RetryPolicy, status: &Status) -> bool {
+    is_transport_failure(status) || policy.retryable_codes.contains(&status.code())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::VecDeque, rc::Rc, time::Duration};
+
+    use bytes::Bytes;
+    use futures_util::{FutureExt, StreamExt};
+    use http::Request;
+    use tonic::{Code, client::Grpc};
+
+    use super::*;
+    use crate::{
+        Client, Error,
+        error::JsErrorValue,
+        fetch::{FetchBackend, FetchBody, FetchFuture},
+        mock_fetch::{BytesCodec, GrpcClient, MockFetch, data_frame, trailer_frame},
+        options::FetchOptions,
+    };
+
+    /// Backend answering each request with the next mock and recording request messages
+    #[derive(Default)]
+    struct Sequence {
+        mocks: RefCell<VecDeque<MockFetch>>,
+        messages: RefCell<Vec<Bytes>>,
+    }
+
+    impl FetchBackend for Sequence {
+        fn fetch(
+            &self,
+            request: Request<FetchBody>,
+            options: &FetchOptions,
+            timeout: Option<Duration>,
+        ) -> FetchFuture {
+            let mock = self
+                .mocks
+                .borrow_mut()
+                .pop_front()
+                .expect("unexpected call");
+            let response = mock.fetch(request, options, timeout);
+
+            let body = mock.requests()[0].request.body().clone().unwrap();
+            self.messages.borrow_mut().push(body.slice(5..));
+
+            response
+        }
+    }
+
+    #[test]
+    fn test_resumable_stream() {
+        let truncated: fn(MockFetch) -> MockFetch =
+            |mock| mock.chunk(data_frame(b"lost").slice(..3));
+        let network_error: fn(MockFetch) -> MockFetch = |mock| {
+            mock.error(Error::NetworkError(JsErrorValue::mock_transport_failure(
+                "connection reset",
+            )))
+        };
+        let read_error: fn(MockFetch) -> MockFetch = |mock| {
+            mock.error(Error::StreamReadError(
+                JsErrorValue::mock_transport_failure("connection reset"),
+            ))
+        };
+        let not_found: fn(MockFetch) -> MockFetch =
+            |mock| mock.chunk(trailer_frame("grpc-status: 5\r\n"));
+        let spoofed: fn(MockFetch) -> MockFetch = |mock| {
+            mock.chunk(trailer_frame(
+                "grpc-status: 14\r\nx-transport-failure: true\r\n",
+            ))
+        };
+
+        // Transport failures are resumed from the last message, even though no code is retryable
+        let vals = [
+            (truncated, vec![Ok("1"), Ok("2")], vec!["start", "1"]),
+            (network_error, vec![Ok("1"), Ok("2")], vec!["start", "1"]),
+            (read_error, vec![Ok("1"), Ok("2")], vec!["start", "1"]),
+            (not_found, vec![Ok("1"), Err(Code::NotFound)], vec!["start"]),
+            (
+                spoofed,
+                vec![Ok("1"), Err(Code::Unavailable)],
+                vec!["start"],
+            ),
+        ];
+
+        for (failure, expected, messages) in vals.iter() {
+            let backend = Rc::new(Sequence::default());
+            backend.mocks.borrow_mut().extend([
+                failure(MockFetch::grpc_web().chunk(data_frame(b"1"))),
+                MockFetch::grpc_web()
+                    .chunk(data_frame(b"2"))
+                    .chunk(trailer_frame("grpc-status: 0\r\n")),
+            ]);
+
+            let mut client = Client::new("http://localhost:50051".to_string());
+            client.with_backend(backend.clone());
+
+            let stream = ResumableStream::new_with_policy(
+                RetryPolicy::new()
+                    .initial_backoff(Duration::ZERO)
+                    .retryable_codes(Vec::new()),
+                Bytes::from_static(b"start"),
+                move |request| {
+                    let mut grpc = Grpc::new(GrpcClient(client.clone()));
+
+                    async move {
+                        grpc.ready().await.map_err(Status::from)?;
+                        grpc.server_streaming(
+                            tonic::Request::new(request),
+                            "/echo.Echo/EchoInfiniteStream".parse().unwrap(),
+                            BytesCodec,
+                        )
+                        .await
+                    }
+                },
+                |_, message: &Bytes| message.clone(),
+            );
+
+            let items: Vec<_> = stream
+                .map(|item| match item {
+                    Ok(message) => Ok(String::from_utf8(message.to_vec()).unwrap()),
+                    Err(status) => Err(status.code()),
+                })
+                .collect()
+                .now_or_never()
+                .unwrap();
+            let expected: Vec<_> = expected
+                .iter()
+                .map(|item| item.map(str::to_string))
+                .collect();
+            assert_eq!(items, expected);
+
+            assert_eq!(*backend.messages.borrow(), *messages);
+        }
+    }
+}
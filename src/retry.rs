@@ -0,0 +1,173 @@
+use std::{future::Future, future::poll_fn, pin::Pin, time::Duration};
+
+use http::{HeaderMap, Response};
+use tonic::Code;
+
+use crate::{
+    Error, ResponseBody,
+    options::RetryPolicy,
+    timer::{now, sleep},
+};
+
+const GRPC_STATUS: &str = "grpc-status";
+const GRPC_RETRY_PUSHBACK_MS: &str = "grpc-retry-pushback-ms";
+
+/// Outcome of a single attempt
+enum Outcome {
+    /// Attempt must not be retried
+    Done,
+    /// Attempt may be retried (after given pushback, if any)
+    Retry(Option<Duration>),
+}
+
+/// Calls `attempt` until it succeeds, fails with a non-retryable error or the retry budget is exhausted
+///
+/// `attempt` is called with the time remaining until `timeout` (measured from the first attempt) elapses. Responses of
+/// `unary` calls are read until the first message or the final status is available, other responses are classified
+/// from their headers only.
+pub async fn retry<F, Fut>(
+    policy: &RetryPolicy,
+    timeout: Option<Duration>,
+    unary: bool,
+    mut attempt: F,
+) -> Result<Response<ResponseBody>, Error>
+where
+    F: FnMut(Option<Duration>) -> Fut,
+    Fut: Future<Output = Result<Response<ResponseBody>, Error>>,
+{
+    let start = now();
+    let elapsed = || Duration::from_secs_f64(((now() - start) / 1000.0).max(0.0));
+    let remaining = |elapsed: Duration| timeout.map(|timeout| timeout.saturating_sub(elapsed));
+
+    let mut attempts = 1;
+
+    loop {
+        let mut result = attempt(remaining(elapsed())).await;
+
+        let outcome = match result {
+            Ok(ref mut response) if unary => {
+                let body = response.body_mut();
+
+                match poll_fn(|cx| Pin::new(&mut *body).poll_prefetch(cx)).await {
+                    Ok(()) => body
+                        .trailers_only()
+                        .map(|trailers| classify_trailers(policy, trailers))
+                        .unwrap_or(Outcome::Done),
+                    Err(error) => {
+                        let outcome = classify_error(policy, &error);
+                        result = Err(error);
+                        outcome
+                    }
+                }
+            }
+            // Waiting for the first message of a stream would delay initial metadata, or never resolve for idle streams
+            Ok(ref response) => response
+                .body()
+                .trailers_only()
+                .map(|trailers| classify_trailers(policy, trailers))
+                .unwrap_or(Outcome::Done),
+            Err(ref error) => classify_error(policy, error),
+        };
+
+        let Outcome::Retry(pushback) = outcome else {
+            return result;
+        };
+
+        if attempts >= policy.max_attempts {
+            return result;
+        }
+
+        let backoff = pushback.unwrap_or_else(|| jitter(policy.backoff(attempts)));
+
+        if let Some(remaining) = remaining(elapsed())
+            && backoff >= remaining
+        {
+            return result;
+        }
+
+        // Drop previous response (this aborts the underlying request) before waiting
+        drop(result);
+        sleep(backoff).await;
+
+        attempts += 1;
+    }
+}
+
+fn classify_error(policy: &RetryPolicy, error: &Error) -> Outcome {
+    match error {
+        Error::NetworkError(_) | Error::StreamReadError(_) => Outcome::Retry(None),
+        Error::TonicStatusError(status) if policy.retryable_codes.contains(&status.code()) => {
+            Outcome::Retry(None)
+        }
+        _ => Outcome::Done,
+    }
+}
+
+fn classify_trailers(policy: &RetryPolicy, trailers: &HeaderMap) -> Outcome {
+    let code = trailers
+        .get(GRPC_STATUS)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<i32>().ok())
+        .map(Code::from_i32);
+
+    match code {
+        Some(code) if policy.retryable_codes.contains(&code) => {
+            match trailers.get(GRPC_RETRY_PUSHBACK_MS) {
+                None => Outcome::Retry(None),
+                // Server pushback overrides backoff. Negative or invalid pushback means that the call must not be
+                // retried.
+                Some(value) => match value.to_str().ok().map(|value| value.parse::<u64>()) {
+                    Some(Ok(millis)) => Outcome::Retry(Some(Duration::from_millis(millis))),
+                    _ => Outcome::Done,
+                },
+            }
+        }
+        _ => Outcome::Done,
+    }
+}
+
+/// Returns a random duration between zero and `backoff`
+pub(crate) fn jitter(backoff: Duration) -> Duration {
+    backoff.mul_f64(js_sys::Math::random())
+}
+
+#[cfg(test)]
+mod tests {
+    use http::HeaderValue;
+
+    use super::*;
+
+    fn trailers(status: &'static str, pushback: Option<&'static str>) -> HeaderMap {
+        let mut trailers = HeaderMap::new();
+        trailers.insert(GRPC_STATUS, HeaderValue::from_static(status));
+        if let Some(pushback) = pushback {
+            trailers.insert(GRPC_RETRY_PUSHBACK_MS, HeaderValue::from_static(pushback));
+        }
+        trailers
+    }
+
+    #[test]
+    fn test_classify_trailers() {
+        let policy = RetryPolicy::new();
+
+        let vals = [
+            (trailers("14", None), Some(None)),
+            (
+                trailers("14", Some("250")),
+                Some(Some(Duration::from_millis(250))),
+            ),
+            (trailers("14", Some("-1")), None),
+            (trailers("14", Some("soon")), None),
+            (trailers("0", None), None),
+            (trailers("16", None), None),
+            (HeaderMap::new(), None),
+        ];
+        for (trailers, expected) in vals.iter() {
+            let outcome = match classify_trailers(&policy, trailers) {
+                Outcome::Retry(pushback) => Some(pushback),
+                Outcome::Done => None,
+            };
+            assert_eq!(outcome, *expected);
+        }
+    }
+}
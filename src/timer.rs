@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use js_sys::{Function, Promise};
+use wasm_bindgen::{JsValue, prelude::wasm_bindgen};
+use wasm_bindgen_futures::JsFuture;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_name = "setTimeout")]
+    pub fn set_timeout(handler: &Function, timeout: i32) -> JsValue;
+
+    #[wasm_bindgen(js_name = "clearTimeout")]
+    pub fn clear_timeout(handle: JsValue) -> JsValue;
+}
+
+/// Converts duration to milliseconds accepted by `setTimeout`
+pub fn timeout_millis(duration: Duration) -> i32 {
+    duration.as_millis().try_into().unwrap_or(i32::MAX)
+}
+
+/// Returns current time in milliseconds since the Unix epoch
+#[cfg(target_arch = "wasm32")]
+pub fn now() -> f64 {
+    js_sys::Date::now()
+}
+
+/// Returns current time in milliseconds since the Unix epoch (used by native unit tests)
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0.0, |duration| duration.as_secs_f64() * 1000.0)
+}
+
+/// Waits for given duration using `setTimeout`
+pub async fn sleep(duration: Duration) {
+    let promise = Promise::new(&mut |resolve, _| {
+        set_timeout(&resolve, timeout_millis(duration));
+    });
+
+    // `resolve` is never rejected
+    let _ = JsFuture::from(promise).await;
+}
@@ -27,6 +27,21 @@ async fn test_echo() {
     assert_eq!(response.message, "echo(John)");
 }
 
+#[wasm_bindgen_test]
+async fn test_echo_send_compressed() {
+    let mut client = build_client().send_compressed(CompressionEncoding::Gzip);
+
+    let response = client
+        .echo(EchoRequest {
+            message: "John".to_string(),
+        })
+        .await
+        .expect("success response");
+
+    assert_eq!(response.metadata().get("x-request-encoding").unwrap(), "gzip");
+    assert_eq!(response.into_inner().message, "echo(John)");
+}
+
 #[wasm_bindgen_test]
 async fn test_echo_stream() {
     let mut client = build_client();
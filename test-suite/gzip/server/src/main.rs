@@ -8,7 +8,10 @@ use std::{
 use futures_core::Stream;
 use http::header::HeaderName;
 use proto::echo_server::EchoServer;
-use tonic::{codegen::CompressionEncoding, transport::Server, Request, Response, Status};
+use tonic::{
+    codegen::CompressionEncoding, metadata::MetadataValue, transport::Server, Request, Response,
+    Status,
+};
 use tonic_web::GrpcWebLayer;
 use tower_http::cors::{AllowOrigin, CorsLayer};
 
@@ -27,10 +30,22 @@ impl Echo for EchoService {
     type EchoInfiniteStreamStream = InfiniteMessageStream;
 
     async fn echo(&self, request: Request<EchoRequest>) -> Result<Response<EchoResponse>, Status> {
+        // Report the encoding of the request so that clients can verify request compression
+        let request_encoding = request
+            .metadata()
+            .get("grpc-encoding")
+            .cloned()
+            .unwrap_or_else(|| MetadataValue::from_static("identity"));
+
         let request = request.into_inner();
-        Ok(Response::new(EchoResponse {
+        let mut response = Response::new(EchoResponse {
             message: format!("echo({})", request.message),
-        }))
+        });
+        response
+            .metadata_mut()
+            .insert("x-request-encoding", request_encoding);
+
+        Ok(response)
     }
 
     async fn echo_stream(
@@ -100,24 +115,28 @@ impl Stream for InfiniteMessageStream {
 }
 
 const DEFAULT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
-const DEFAULT_EXPOSED_HEADERS: [&str; 4] = [
+const DEFAULT_EXPOSED_HEADERS: [&str; 5] = [
     "grpc-status",
     "grpc-message",
     "grpc-status-details-bin",
     "grpc-encoding",
+    "x-request-encoding",
 ];
-const DEFAULT_ALLOW_HEADERS: [&str; 5] = [
+const DEFAULT_ALLOW_HEADERS: [&str; 6] = [
     "x-grpc-web",
     "content-type",
     "x-user-agent",
     "grpc-timeout",
+    "grpc-encoding",
     "grpc-accept-encoding",
 ];
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     let addr = "[::1]:50051".parse().unwrap();
-    let echo = EchoServer::new(EchoService).send_compressed(CompressionEncoding::Gzip);
+    let echo = EchoServer::new(EchoService)
+        .send_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Gzip);
 
     Server::builder()
         .accept_http1(true)
@@ -1,8 +1,11 @@
 use std::time::Duration;
 
 use client::proto::{echo_client::EchoClient, EchoRequest};
-use tonic::Code;
-use tonic_web_wasm_client::{options::FetchOptions, Client};
+use tonic::{Code, Request};
+use tonic_web_wasm_client::{
+    options::{Encoding, FetchOptions},
+    Client,
+};
 use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
 
 wasm_bindgen_test_configure!(run_in_browser);
@@ -31,6 +34,50 @@ async fn test_echo() {
     assert_eq!(response.message, "echo(John)");
 }
 
+#[wasm_bindgen_test]
+async fn test_echo_metadata() {
+    let mut client = build_client();
+
+    let response = client
+        .echo(EchoRequest {
+            message: "John".to_string(),
+        })
+        .await
+        .expect("success response");
+
+    let metadata = response.metadata();
+
+    assert_eq!(metadata.get("x-session-id").unwrap(), "session-1");
+    assert_eq!(
+        metadata
+            .get_all_bin("x-session-bin")
+            .iter()
+            .map(|value| value.to_bytes().unwrap().to_vec())
+            .collect::<Vec<_>>(),
+        [vec![0, 1], vec![2, 3]]
+    );
+}
+
+#[wasm_bindgen_test]
+async fn test_echo_text() {
+    let base_url = "http://localhost:50051".to_string();
+
+    let mut wasm_client = Client::new(base_url);
+    wasm_client.with_options(FetchOptions::default().encoding(Encoding::Text));
+
+    let mut client = EchoClient::new(wasm_client);
+
+    let response = client
+        .echo(EchoRequest {
+            message: "John".to_string(),
+        })
+        .await
+        .expect("success response")
+        .into_inner();
+
+    assert_eq!(response.message, "echo(John)");
+}
+
 #[wasm_bindgen_test]
 async fn test_echo_timeout() {
     let mut client = build_client();
@@ -45,6 +92,37 @@ async fn test_echo_timeout() {
     assert_eq!(error.code(), Code::DeadlineExceeded);
 }
 
+#[wasm_bindgen_test]
+async fn test_echo_grpc_timeout() {
+    let base_url = "http://localhost:50051".to_string();
+    let mut client = EchoClient::new(Client::new(base_url));
+
+    let mut request = Request::new(EchoRequest {
+        message: "John".to_string(),
+    });
+    request.set_timeout(Duration::from_secs(1));
+
+    let error = client.echo_timeout(request).await.unwrap_err();
+
+    assert_eq!(error.code(), Code::DeadlineExceeded);
+}
+
+#[wasm_bindgen_test]
+async fn test_echo_network_error() {
+    // Nothing listens on this port
+    let base_url = "http://localhost:50052".to_string();
+    let mut client = EchoClient::new(Client::new(base_url));
+
+    let error = client
+        .echo(EchoRequest {
+            message: "John".to_string(),
+        })
+        .await
+        .unwrap_err();
+
+    assert_eq!(error.code(), Code::Unavailable);
+}
+
 #[wasm_bindgen_test]
 async fn test_echo_stream() {
     let mut client = build_client();
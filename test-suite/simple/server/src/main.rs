@@ -8,7 +8,7 @@ use std::{
 use futures_core::Stream;
 use http::header::HeaderName;
 use proto::echo_server::EchoServer;
-use tonic::{transport::Server, Request, Response, Status};
+use tonic::{metadata::MetadataValue, transport::Server, Request, Response, Status};
 use tonic_web::GrpcWebLayer;
 use tower_http::cors::{AllowOrigin, CorsLayer};
 
@@ -28,9 +28,16 @@ impl Echo for EchoService {
 
     async fn echo(&self, request: Request<EchoRequest>) -> Result<Response<EchoResponse>, Status> {
         let request = request.into_inner();
-        Ok(Response::new(EchoResponse {
+        let mut response = Response::new(EchoResponse {
             message: format!("echo({})", request.message),
-        }))
+        });
+
+        let metadata = response.metadata_mut();
+        metadata.insert("x-session-id", MetadataValue::from_static("session-1"));
+        metadata.append_bin("x-session-bin", MetadataValue::from_bytes(&[0, 1]));
+        metadata.append_bin("x-session-bin", MetadataValue::from_bytes(&[2, 3]));
+
+        Ok(response)
     }
 
     async fn echo_timeout(
@@ -155,10 +162,12 @@ impl Stream for ErrorAfterMessagesStream {
 }
 
 const DEFAULT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
-const DEFAULT_EXPOSED_HEADERS: [HeaderName; 3] = [
+const DEFAULT_EXPOSED_HEADERS: [HeaderName; 5] = [
     HeaderName::from_static("grpc-status"),
     HeaderName::from_static("grpc-message"),
     HeaderName::from_static("grpc-status-details-bin"),
+    HeaderName::from_static("x-session-id"),
+    HeaderName::from_static("x-session-bin"),
 ];
 const DEFAULT_ALLOW_HEADERS: [HeaderName; 4] = [
     HeaderName::from_static("x-grpc-web"),